@@ -0,0 +1,116 @@
+use crate::dryer::controller::pid::Gains;
+use serde::Serialize;
+use std::f32::consts::PI;
+use std::time::Duration;
+
+/// Upper bound for a whole autotune run
+pub const TIMEOUT: Duration = Duration::from_secs(2 * 60 * 60);
+/// Number of full oscillations averaged into the result
+pub const CYCLES: usize = 4;
+
+/// Relay output amplitude, the heater swings between 0.0 and 1.0 around 0.5
+const RELAY_AMPLITUDE: f32 = 0.5;
+/// Noise band around the target to avoid relay chatter
+const RELAY_HYSTERESIS: f32 = 0.5;
+
+#[derive(Debug, Serialize)]
+pub struct Progress {
+    pub cycle: usize,
+    pub cycles: usize,
+    pub gains: Option<Gains>,
+}
+
+/// Åström–Hägglund relay experiment: the heater is switched fully on below the target and off
+/// above it, the resulting oscillation gives the ultimate gain and period of the chamber.
+pub struct RelayAutotune {
+    target: f32,
+    cycles: usize,
+    on: bool,
+    elapsed: Duration,
+    extreme: f32,
+    extreme_at: Duration,
+    highs: Vec<(f32, Duration)>,
+    lows: Vec<f32>,
+}
+
+impl RelayAutotune {
+    pub fn new(target: f32, cycles: usize) -> Self {
+        Self {
+            target,
+            cycles,
+            on: true,
+            elapsed: Duration::ZERO,
+            extreme: f32::MAX,
+            extreme_at: Duration::ZERO,
+            highs: Vec::new(),
+            lows: Vec::new(),
+        }
+    }
+
+    /// Relay output (0.0 or 1.0) for the current reading
+    pub fn update(&mut self, value: f32, dt: Duration) -> f32 {
+        self.elapsed += dt;
+        if self.on {
+            self.extreme = self.extreme.min(value);
+            if value > self.target + RELAY_HYSTERESIS {
+                self.on = false;
+                self.lows.push(self.extreme);
+                self.extreme = value;
+                self.extreme_at = self.elapsed;
+            }
+        } else {
+            if value > self.extreme {
+                self.extreme = value;
+                self.extreme_at = self.elapsed;
+            }
+            if value < self.target - RELAY_HYSTERESIS {
+                self.on = true;
+                self.highs.push((self.extreme, self.extreme_at));
+                self.extreme = value;
+            }
+        }
+        if self.on {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// The first low (ambient start) and high (initial overshoot) are not part of the oscillation
+    pub fn is_done(&self) -> bool {
+        self.highs.len() > self.cycles && self.lows.len() > self.cycles
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            cycle: self.highs.len().saturating_sub(1).min(self.cycles),
+            cycles: self.cycles,
+            gains: self.gains(),
+        }
+    }
+
+    /// Ziegler–Nichols PID gains from the measured oscillation
+    pub fn gains(&self) -> Option<Gains> {
+        if !self.is_done() {
+            return None;
+        }
+        let highs = &self.highs[1..=self.cycles];
+        let lows = &self.lows[1..=self.cycles];
+        let amplitude = highs
+            .iter()
+            .zip(lows)
+            .map(|((high, _), low)| (high - low) / 2.0)
+            .sum::<f32>()
+            / self.cycles as f32;
+        let period = (highs[self.cycles - 1].1 - highs[0].1).as_secs_f32() / (self.cycles - 1) as f32;
+        if amplitude <= 0.0 || period <= 0.0 {
+            return None;
+        }
+        let ultimate_gain = 4.0 * RELAY_AMPLITUDE / (PI * amplitude);
+        Some(Gains::new(
+            0.6 * ultimate_gain,
+            1.2 * ultimate_gain / period,
+            0.075 * ultimate_gain * period,
+        ))
+    }
+}
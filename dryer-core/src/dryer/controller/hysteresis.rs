@@ -0,0 +1,40 @@
+use crate::dryer::heater::TemperatureController;
+use std::time::Duration;
+
+/// Bang-bang control: full power until the chamber is `above` degrees over the target,
+/// then off until it drops `below` degrees under the target.
+pub struct Hysteresis {
+    below: f32,
+    above: f32,
+    on: bool,
+}
+
+impl Hysteresis {
+    pub fn new(below: f32, above: f32) -> Self {
+        Self {
+            below,
+            above,
+            on: true,
+        }
+    }
+}
+
+impl TemperatureController for Hysteresis {
+    fn output(&mut self, target: f32, value: f32, _dt: Duration) -> f32 {
+        if value > target + self.above {
+            self.on = false;
+        }
+        if value < target - self.below {
+            self.on = true;
+        }
+        if self.on {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn reset(&mut self) {
+        self.on = true;
+    }
+}
@@ -0,0 +1,3 @@
+pub mod autotune;
+pub mod hysteresis;
+pub mod pid;
@@ -0,0 +1,130 @@
+use crate::dryer::heater::TemperatureController;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Gains {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+}
+
+impl Gains {
+    pub fn new(kp: f32, ki: f32, kd: f32) -> Self {
+        Self { kp, ki, kd }
+    }
+}
+
+pub struct Pid {
+    gains: Gains,
+    integral: f32,
+    last_value: Option<f32>,
+    min: f32,
+    max: f32,
+}
+
+impl Pid {
+    pub fn new(gains: Gains) -> Self {
+        Self {
+            gains,
+            integral: 0.0,
+            last_value: None,
+            min: 0.0,
+            max: 1.0,
+        }
+    }
+}
+
+impl TemperatureController for Pid {
+    fn output(&mut self, target: f32, value: f32, dt: Duration) -> f32 {
+        let dt = dt.as_secs_f32();
+        let Gains { kp, ki, kd } = self.gains;
+        let error = target - value;
+        // Derivative on measurement, so a new target doesn't kick the output
+        let derivative = match self.last_value {
+            Some(last) if dt > 0.0 => -(value - last) / dt,
+            _ => 0.0,
+        };
+        self.last_value = Some(value);
+
+        let proportional = kp * error;
+        let integral = self.integral + ki * error * dt;
+        let unclamped = proportional + integral + kd * derivative;
+        // Anti-windup: stop integrating while the output is saturated in the error's direction
+        let saturated = (unclamped > self.max && error > 0.0) || (unclamped < self.min && error < 0.0);
+        if !saturated {
+            self.integral = integral.clamp(self.min, self.max);
+        }
+        (proportional + self.integral + kd * derivative).clamp(self.min, self.max)
+    }
+
+    fn reset(&mut self) {
+        self.integral = 0.0;
+        self.last_value = None;
+    }
+
+    fn tune(&mut self, gains: Gains) {
+        self.gains = gains;
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn saturated_output_does_not_wind_up() {
+        let mut pid = Pid::new(Gains::new(0.08, 0.0005, 0.0));
+        for _ in 0..3600 {
+            assert_eq!(pid.output(65.0, 20.0, SEC), 1.0);
+        }
+        // Only the proportional part is left once the chamber gets close
+        let output = pid.output(65.0, 64.0, SEC);
+        assert!((output - 0.0805).abs() < 1e-4, "output {}", output);
+    }
+
+    #[test]
+    fn integral_grows_while_unsaturated() {
+        let mut pid = Pid::new(Gains::new(0.08, 0.0005, 0.0));
+        let first = pid.output(65.0, 64.0, SEC);
+        for _ in 0..600 {
+            pid.output(65.0, 64.0, SEC);
+        }
+        let later = pid.output(65.0, 64.0, SEC);
+        assert!((later - first - 0.3).abs() < 1e-3, "{} -> {}", first, later);
+    }
+
+    #[test]
+    fn integral_unwinds_above_target() {
+        let mut pid = Pid::new(Gains::new(0.0, 0.01, 0.0));
+        for _ in 0..200 {
+            pid.output(65.0, 64.0, SEC);
+        }
+        assert!(pid.output(65.0, 64.0, SEC) > 0.99);
+        for _ in 0..50 {
+            pid.output(65.0, 66.0, SEC);
+        }
+        assert!(pid.output(65.0, 66.0, SEC) < 0.5);
+    }
+
+    #[test]
+    fn target_change_does_not_kick_derivative() {
+        let mut pid = Pid::new(Gains::new(0.0, 0.0, 10.0));
+        assert_eq!(pid.output(50.0, 40.0, SEC), 0.0);
+        assert_eq!(pid.output(65.0, 40.0, SEC), 0.0);
+        assert!(pid.output(65.0, 39.9, SEC) > 0.9);
+    }
+
+    #[test]
+    fn reset_clears_integral() {
+        let mut pid = Pid::new(Gains::new(0.0, 0.01, 0.0));
+        for _ in 0..50 {
+            pid.output(65.0, 64.0, SEC);
+        }
+        pid.reset();
+        assert!(pid.output(65.0, 64.0, SEC) <= 0.01 + f32::EPSILON);
+    }
+}
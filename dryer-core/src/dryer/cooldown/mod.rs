@@ -0,0 +1,15 @@
+use std::time::Duration;
+
+/// Run after a completed cycle with the heater off and the fan at max, so residual element heat
+/// doesn't soak the spool. Ends once the chamber is at or below `below`, after `timeout`, or on `/stop`
+#[derive(Debug, Clone, Copy)]
+pub struct CoolDown {
+    pub below: f32,
+    pub timeout: Duration,
+}
+
+impl CoolDown {
+    pub fn new(below: f32, timeout: Duration) -> Self {
+        Self { below, timeout }
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Humidity is averaged over this period before it enters the slope window
+const SAMPLE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Humidity based end of a drying cycle, the cycle duration stays as a cap
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct HumidityEnd {
+    /// Done once RH stays below this for `hold` seconds, %
+    pub below: Option<f32>,
+    #[serde(default = "default_hold")]
+    pub hold: u64,
+    /// Done once RH falls slower than this over `window` seconds, %/h
+    pub min_fall: Option<f32>,
+    #[serde(default = "default_window")]
+    pub window: u64,
+}
+
+fn default_hold() -> u64 {
+    600
+}
+
+fn default_window() -> u64 {
+    1800
+}
+
+impl HumidityEnd {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.below.is_none() && self.min_fall.is_none() {
+            Err(anyhow!("humidity end needs `below` or `min_fall`"))?
+        }
+        if self.min_fall.is_some() && self.window < 2 * SAMPLE_PERIOD.as_secs() {
+            Err(anyhow!("humidity window must be at least {}s", 2 * SAMPLE_PERIOD.as_secs()))?
+        }
+        Ok(())
+    }
+}
+
+pub struct DrynessDetector {
+    end: HumidityEnd,
+    below_for: Duration,
+    elapsed: Duration,
+    period: Duration,
+    sum: f32,
+    count: u32,
+    /// Per period averages as (hours, RH)
+    samples: VecDeque<(f32, f32)>,
+}
+
+impl DrynessDetector {
+    pub fn new(end: HumidityEnd) -> Self {
+        Self {
+            end,
+            below_for: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            period: Duration::ZERO,
+            sum: 0.0,
+            count: 0,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Starts over, the hold and the slope only count an unbroken stretch of drying
+    pub fn reset(&mut self) {
+        *self = Self::new(self.end);
+    }
+
+    /// Feeds the chamber RH, reports whether the material is considered dry
+    pub fn update(&mut self, humidity: f32, dt: Duration) -> bool {
+        self.elapsed += dt;
+        let below = match self.end.below {
+            Some(threshold) if humidity < threshold => {
+                self.below_for += dt;
+                self.below_for >= Duration::from_secs(self.end.hold)
+            }
+            _ => {
+                self.below_for = Duration::ZERO;
+                false
+            }
+        };
+        let settled = match (self.end.min_fall, self.sample(humidity, dt)) {
+            (Some(min_fall), Some(fall)) => fall < min_fall,
+            _ => false,
+        };
+        below || settled
+    }
+
+    /// Averages RH over the sample period, returns the fall rate once the window is full
+    fn sample(&mut self, humidity: f32, dt: Duration) -> Option<f32> {
+        self.end.min_fall?;
+        self.sum += humidity;
+        self.count += 1;
+        self.period += dt;
+        if self.period < SAMPLE_PERIOD {
+            return None;
+        }
+        let size = (self.end.window / SAMPLE_PERIOD.as_secs()) as usize;
+        self.samples.push_back((self.elapsed.as_secs_f32() / 3600.0, self.sum / self.count as f32));
+        while self.samples.len() > size {
+            self.samples.pop_front();
+        }
+        self.period = Duration::ZERO;
+        self.sum = 0.0;
+        self.count = 0;
+        if self.samples.len() < size {
+            return None;
+        }
+        Some(-slope(&self.samples))
+    }
+}
+
+/// Least squares slope of RH over time, %/h
+fn slope(samples: &VecDeque<(f32, f32)>) -> f32 {
+    let n = samples.len() as f32;
+    let mean_x = samples.iter().map(|s| s.0).sum::<f32>() / n;
+    let mean_y = samples.iter().map(|s| s.1).sum::<f32>() / n;
+    let (num, den) = samples.iter().fold((0.0, 0.0), |(num, den), (x, y)| {
+        (num + (x - mean_x) * (y - mean_y), den + (x - mean_x).powi(2))
+    });
+    num / den
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    fn below(threshold: f32, hold: u64) -> HumidityEnd {
+        HumidityEnd { below: Some(threshold), hold, min_fall: None, window: default_window() }
+    }
+
+    fn falling(min_fall: f32, window: u64) -> HumidityEnd {
+        HumidityEnd { below: None, hold: default_hold(), min_fall: Some(min_fall), window }
+    }
+
+    /// Seconds until the detector reports dry, RH given per second
+    fn dry_after(detector: &mut DrynessDetector, humidity: impl Fn(u64) -> f32, limit: u64) -> Option<u64> {
+        (1..=limit).find(|t| detector.update(humidity(*t), SEC))
+    }
+
+    #[test]
+    fn below_needs_the_whole_hold() {
+        let mut detector = DrynessDetector::new(below(10.0, 600));
+        assert_eq!(dry_after(&mut detector, |_| 9.0, 3600), Some(600));
+    }
+
+    #[test]
+    fn reading_above_restarts_hold() {
+        let mut detector = DrynessDetector::new(below(10.0, 600));
+        assert_eq!(dry_after(&mut detector, |_| 9.0, 500), None);
+        assert!(!detector.update(11.0, SEC));
+        assert_eq!(dry_after(&mut detector, |_| 9.0, 3600), Some(600));
+    }
+
+    #[test]
+    fn reset_restarts_hold() {
+        let mut detector = DrynessDetector::new(below(10.0, 600));
+        assert_eq!(dry_after(&mut detector, |_| 9.0, 500), None);
+        detector.reset();
+        assert_eq!(dry_after(&mut detector, |_| 9.0, 3600), Some(600));
+    }
+
+    #[test]
+    fn falling_humidity_is_not_dry() {
+        let mut detector = DrynessDetector::new(falling(0.5, 1800));
+        // 2%/h down from 40%
+        assert_eq!(dry_after(&mut detector, |t| 40.0 - 2.0 * t as f32 / 3600.0, 4 * 3600), None);
+    }
+
+    #[test]
+    fn flat_humidity_is_dry_once_the_window_fills() {
+        let mut detector = DrynessDetector::new(falling(0.5, 1800));
+        assert_eq!(dry_after(&mut detector, |_| 8.0, 3600), Some(1800));
+
+        // Falling first, the window has to hold only flat samples before the slope drops below the limit
+        let mut detector = DrynessDetector::new(falling(0.5, 1800));
+        assert_eq!(dry_after(&mut detector, |t| 40.0 - 10.0 * t as f32 / 3600.0, 3600), None);
+        let dry = dry_after(&mut detector, |_| 30.0, 3600).unwrap();
+        assert!((900..=1800).contains(&dry), "dry after {}s", dry);
+    }
+
+    #[test]
+    fn reset_drops_the_slope_window() {
+        let mut detector = DrynessDetector::new(falling(0.5, 1800));
+        assert_eq!(dry_after(&mut detector, |_| 8.0, 1700), None);
+        detector.reset();
+        assert_eq!(dry_after(&mut detector, |_| 8.0, 3600), Some(1800));
+    }
+
+    #[test]
+    fn validate() {
+        assert!(below(10.0, 600).validate().is_ok());
+        assert!(falling(0.5, 120).validate().is_ok());
+        assert!(falling(0.5, 119).validate().is_err());
+        // The window only matters for the slope
+        assert!(HumidityEnd { window: 0, ..below(10.0, 600) }.validate().is_ok());
+        assert!(HumidityEnd { below: None, hold: 600, min_fall: None, window: 1800 }.validate().is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::dryer::heater::FanSpeed;
+use std::time::Duration;
+
+/// Integral gain of the RPM loop, %/s per RPM of error
+const RPM_GAIN: f32 = 0.01;
+
+/// Airflow of the named speeds, percent of full speed
+#[derive(Debug, Clone, Copy)]
+pub struct FanPresets {
+    pub low: f32,
+    pub middle: f32,
+    pub max: f32,
+}
+
+impl Default for FanPresets {
+    fn default() -> Self {
+        Self { low: 30.0, middle: 50.0, max: 100.0 }
+    }
+}
+
+impl FanPresets {
+    pub fn new(low: f32, middle: f32, max: f32) -> Self {
+        Self { low, middle, max }
+    }
+
+    /// Without a tachometer an RPM target runs the fan at the max preset
+    pub fn percent(&self, speed: FanSpeed) -> f32 {
+        match speed {
+            FanSpeed::Off => 0.0,
+            FanSpeed::Low => self.low,
+            FanSpeed::Middle => self.middle,
+            FanSpeed::Max => self.max,
+            FanSpeed::Percent(percent) => percent.clamp(0.0, 100.0),
+            FanSpeed::Rpm(_) => self.max,
+        }
+    }
+}
+
+/// Per fan PWM calibration: below `min_duty` percent the motor stalls,
+/// and from standstill it needs a `kick` at full duty to start reliably
+#[derive(Debug, Clone, Copy)]
+pub struct FanCalibration {
+    pub min_duty: f32,
+    pub kick: Duration,
+}
+
+impl FanCalibration {
+    pub fn new(min_duty: f32, kick: Duration) -> Self {
+        Self { min_duty: min_duty.clamp(0.0, 100.0), kick }
+    }
+
+    /// PWM duty in 0..1 for the requested airflow, scaled into the range where the fan spins
+    pub fn duty(&self, percent: f32) -> f32 {
+        if percent <= 0.0 {
+            return 0.0;
+        }
+        (self.min_duty + (100.0 - self.min_duty) * percent.min(100.0) / 100.0) / 100.0
+    }
+}
+
+/// Holds a target RPM on tach feedback: feed-forward from the rated speed plus an integral trim
+/// for the fan's own curve and the filter load
+pub struct RpmLoop {
+    max_rpm: f32,
+    trim: f32,
+}
+
+impl RpmLoop {
+    /// `max_rpm` is the fan's speed at full duty
+    pub fn new(max_rpm: f32) -> Self {
+        Self { max_rpm: max_rpm.max(1.0), trim: 0.0 }
+    }
+
+    pub fn reset(&mut self) {
+        self.trim = 0.0;
+    }
+
+    /// Airflow percent for `target`, `rpm` is the last measured speed
+    pub fn percent(&mut self, target: f32, rpm: Option<f32>, dt: Duration) -> f32 {
+        if target <= 0.0 {
+            self.reset();
+            return 0.0;
+        }
+        let feed_forward = target / self.max_rpm * 100.0;
+        if let Some(rpm) = rpm {
+            self.trim = (self.trim + RPM_GAIN * (target - rpm) * dt.as_secs_f32()).clamp(-50.0, 50.0);
+        }
+        (feed_forward + self.trim).clamp(0.0, 100.0)
+    }
+}
@@ -0,0 +1,632 @@
+use crate::dryer::controller::autotune::{self, RelayAutotune};
+use crate::dryer::controller::pid::Gains;
+use crate::dryer::cooldown::CoolDown;
+use crate::dryer::dryness::DrynessDetector;
+use crate::dryer::fan::{FanPresets, RpmLoop};
+use crate::dryer::phase::{Phase, PhaseMachine};
+use crate::dryer::power::TimeProportioned;
+use crate::dryer::profile::Profile;
+use crate::dryer::program::{self, Program};
+use crate::dryer::safety::{FaultLatch, RunawayDetector, StallDetector};
+use crate::dryer::sensor::humidity::Humidity;
+use crate::dryer::setpoint::Setpoint;
+use crate::dryer::storage::{self, Storage};
+use crate::dryer::{Cycle, State};
+use crate::time::clock::Clock;
+use crate::time::timer::{SyncTimer, Tick, TICK};
+use anyhow::{anyhow, Error};
+use crossbeam_channel::Sender;
+use embedded_hal::digital::OutputPin;
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub trait TempSensor {
+    /// Starts a conversion that the next `read_celsius` picks up, so the control loop doesn't wait for it
+    fn start_conversion(&mut self) -> anyhow::Result<(), Error> {
+        Ok(())
+    }
+
+    fn read_celsius(&mut self) -> anyhow::Result<f32, Error>;
+
+    /// Samples dropped as implausible since start, for telemetry
+    fn rejected_samples(&self) -> u32 {
+        0
+    }
+}
+
+pub trait HumiditySensor {
+    fn read_humidity(&mut self) -> anyhow::Result<Humidity, Error>;
+}
+
+/// Named presets, `{"percent": 65}` for an exact airflow or `{"rpm": 1500}` held on tach feedback
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FanSpeed {
+    Middle,
+    Max,
+    Low,
+    Off,
+    Percent(f32),
+    Rpm(f32),
+}
+
+pub trait FanSpeedRegulator {
+    /// Airflow in percent of full speed, 0 stops the fan
+    fn set_percent(&mut self, percent: f32) -> anyhow::Result<(), Error>;
+}
+
+pub trait Tachometer {
+    /// Fan speed averaged since the previous read
+    fn rpm(&mut self) -> anyhow::Result<f32, Error>;
+}
+
+pub trait TemperatureController {
+    /// Heater output in range 0.0..=1.0 for the current reading
+    fn output(&mut self, target: f32, value: f32, dt: Duration) -> f32;
+
+    fn reset(&mut self);
+
+    /// Applies gains found by autotune, controllers without gains ignore them
+    fn tune(&mut self, _gains: Gains) {}
+}
+
+pub struct Heater<P: OutputPin, S: TempSensor, F: FanSpeedRegulator, C: TemperatureController> {
+    power: TimeProportioned<P>,
+    sensor: S,
+    fan: F,
+    fan_presets: FanPresets,
+    fan_percent: f32,
+    tachometer: Option<Box<dyn Tachometer>>,
+    fan_rpm: Option<f32>,
+    rpm_loop: Option<RpmLoop>,
+    stall: Option<StallDetector>,
+    no_airflow: bool,
+    controller: C,
+    runaway: RunawayDetector,
+    faults: FaultLatch,
+    element: Option<Box<dyn TempSensor>>,
+    element_max: f32,
+    element_temperature: Option<f32>,
+    ambient: Option<Box<dyn TempSensor>>,
+    ambient_temperature: Option<f32>,
+    humidity_sensor: Option<Box<dyn HumiditySensor>>,
+    humidity: Option<Humidity>,
+    setpoint: Option<Setpoint>,
+    cool_down: Option<CoolDown>,
+    session: Option<Uuid>,
+}
+
+impl<P: OutputPin, S: TempSensor, F: FanSpeedRegulator, C: TemperatureController> Heater<P, S, F, C> {
+    pub fn new(
+        power: TimeProportioned<P>,
+        sensor: S,
+        fan: F,
+        controller: C,
+        runaway: RunawayDetector,
+        faults: FaultLatch,
+    ) -> Self {
+        Heater {
+            power,
+            sensor,
+            fan,
+            fan_presets: FanPresets::default(),
+            fan_percent: 0.0,
+            tachometer: None,
+            fan_rpm: None,
+            rpm_loop: None,
+            stall: None,
+            no_airflow: false,
+            controller,
+            runaway,
+            faults,
+            element: None,
+            element_max: f32::MAX,
+            element_temperature: None,
+            ambient: None,
+            ambient_temperature: None,
+            humidity_sensor: None,
+            humidity: None,
+            setpoint: None,
+            cool_down: None,
+            session: None,
+        }
+    }
+
+    /// Heating element probe, the heater is held off while it reads `max` or above
+    pub fn with_element_sensor(mut self, sensor: Box<dyn TempSensor>, max: f32) -> Self {
+        self.element = Some(sensor);
+        self.element_max = max;
+        self
+    }
+
+    pub fn with_ambient_sensor(mut self, sensor: Box<dyn TempSensor>) -> Self {
+        self.ambient = Some(sensor);
+        self
+    }
+
+    pub fn with_humidity_sensor(mut self, sensor: Box<dyn HumiditySensor>) -> Self {
+        self.humidity_sensor = Some(sensor);
+        self
+    }
+
+    pub fn with_fan_presets(mut self, presets: FanPresets) -> Self {
+        self.fan_presets = presets;
+        self
+    }
+
+    /// Fan tach feedback: RPM telemetry, `{"rpm": ..}` fan speeds scaled from `max_rpm`,
+    /// a `FanStalled` fault from `stall` while heating, and its minimum speed for the airflow interlock
+    pub fn with_tachometer(mut self, tachometer: Box<dyn Tachometer>, max_rpm: f32, stall: StallDetector) -> Self {
+        self.tachometer = Some(tachometer);
+        self.rpm_loop = Some(RpmLoop::new(max_rpm));
+        self.stall = Some(stall);
+        self
+    }
+
+    /// Source of target changes applied to a running drying cycle
+    pub fn with_setpoint(mut self, setpoint: Setpoint) -> Self {
+        self.setpoint = Some(setpoint);
+        self
+    }
+
+    pub fn with_cool_down(mut self, cool_down: CoolDown) -> Self {
+        self.cool_down = Some(cool_down);
+        self
+    }
+
+    /// The fan goes first, so the heater output sees whether it accepted the command.
+    /// Returns the duty that reached the heater
+    fn heat(&mut self, output: f32, fan: FanSpeed) -> anyhow::Result<f32, Error> {
+        self.set_fan(fan)?;
+        self.power(output)
+    }
+
+    fn cooling(&mut self, fan: FanSpeed) -> anyhow::Result<(), Error> {
+        self.controller.reset();
+        self.power_off()?;
+        self.set_fan(fan)?;
+        Ok(())
+    }
+
+    fn set_fan(&mut self, speed: FanSpeed) -> anyhow::Result<(), Error> {
+        let percent = match (speed, self.rpm_loop.as_mut()) {
+            (FanSpeed::Rpm(target), Some(rpm_loop)) => rpm_loop.percent(target, self.fan_rpm, TICK),
+            (speed, rpm_loop) => {
+                if let Some(rpm_loop) = rpm_loop {
+                    rpm_loop.reset();
+                }
+                self.fan_presets.percent(speed)
+            }
+        };
+        // A fan that refused the command is taken as stopped
+        self.fan_percent = 0.0;
+        self.fan.set_percent(percent)?;
+        self.fan_percent = percent;
+        Ok(())
+    }
+
+    /// Held off by a latched fault, the airflow interlock or the element limit, the duty is 0
+    fn power(&mut self, output: f32) -> anyhow::Result<f32, Error> {
+        if self.faults.get().is_some() || !self.airflow(output) {
+            // Cut right away, not at the end of the slow PWM window
+            self.power_off()?;
+            return Ok(0.0);
+        }
+        let duty = self.element_limit(output);
+        self.power.set_duty(duty);
+        self.power.update(TICK)?;
+        Ok(duty)
+    }
+
+    /// Airflow interlock: the fan is commanded on and, with a tachometer,
+    /// confirmed above the stall speed by the reading of this tick
+    fn airflow(&mut self, output: f32) -> bool {
+        let spinning = match (&self.stall, self.fan_rpm) {
+            (None, _) => true,
+            (Some(stall), Some(rpm)) => rpm >= stall.min_rpm(),
+            (Some(_), None) => false,
+        };
+        let airflow = self.fan_percent > 0.0 && spinning;
+        let held = output > 0.0 && !airflow;
+        if held && !self.no_airflow {
+            warn!("heater held off: no airflow");
+        }
+        self.no_airflow = held;
+        airflow
+    }
+
+    /// No output while the element is too hot, or its configured probe can't be read
+    fn element_limit(&self, output: f32) -> f32 {
+        match (&self.element, self.element_temperature) {
+            (None, _) => output,
+            (Some(_), Some(value)) if value < self.element_max => output,
+            _ => 0.0,
+        }
+    }
+
+    fn power_off(&mut self) -> anyhow::Result<(), Error> {
+        self.power.off()
+    }
+
+    fn state(&self, value: f32, phases: &PhaseMachine, tick: Tick) -> State {
+        State::new(true, value)
+            .with_session(self.session)
+            .with_phase(phases.current(), phases.entered())
+            .with_remaining(tick.remaining, tick.ends_at)
+            .with_started_at(tick.started_at)
+            .with_rejected_samples(self.sensor.rejected_samples())
+            .with_temperatures(self.element_temperature, self.ambient_temperature)
+            .with_humidity(self.humidity)
+            .with_fan_rpm(self.fan_rpm)
+    }
+
+    /// Ends the cycle in `Finished` if it ran its course, `Idle` if it was stopped, or `Fault`
+    fn finish<K: Clock>(&self, phases: &mut PhaseMachine, completed: bool, timer: &SyncTimer<K>) -> Result<State, Error> {
+        let next = match (self.faults.get(), completed) {
+            (Some(_), _) => Phase::Fault,
+            (None, true) => Phase::Finished,
+            (None, false) => Phase::Idle,
+        };
+        phases.enter(next, timer.now())?;
+        Ok(State::inactive()
+            .with_session(self.session)
+            .with_phase(phases.current(), phases.entered())
+            .with_started_at(timer.started_at())
+            .with_fault(self.faults.get()))
+    }
+
+    /// Feeds the runaway and stall detectors and reports whether the cycle has to end on a latched fault.
+    /// The runaway check looks at the `duty` that reached the heater, the stall check at the requested `output`,
+    /// a jammed fan holds the heater off through the interlock
+    fn faulted(&mut self, target: f32, value: f32, output: f32, duty: f32) -> Result<bool, Error> {
+        if let Err(fault) = self.runaway.check(target, value, duty, TICK) {
+            error!("{}", fault);
+            self.faults.trip(fault);
+        }
+        let heating = output > 0.0 && self.fan_percent > 0.0;
+        if let Some(Err(fault)) = self.stall.as_mut().map(|s| s.check(heating, self.fan_rpm, TICK)) {
+            error!("{}", fault);
+            self.faults.trip(fault);
+        }
+        if self.faults.get().is_some() {
+            self.power_off()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Runs the configured cool-down after a completed cycle, returns the timer to take the end time from
+    fn cool_down<K: Clock>(
+        &mut self,
+        timer: SyncTimer<K>,
+        completed: bool,
+        phases: &mut PhaseMachine,
+        state: &Sender<State>,
+    ) -> Result<SyncTimer<K>, Error> {
+        let Some(cool_down) = self.cool_down else {
+            return Ok(timer);
+        };
+        if !completed || self.faults.get().is_some() {
+            return Ok(timer);
+        }
+        let mut failed_requests = 0;
+        // The timeout runs by the clock, pause and adjust don't hold the cool-down, stop ends it
+        let timer = timer.next_stage();
+        let until = timer.now() + cool_down.timeout;
+        self.controller.reset();
+        phases.enter(Phase::CoolDown, timer.now())?;
+        timer.next_sec_until(|_| {
+            let now = timer.now();
+            if now >= until {
+                return Ok(true);
+            }
+            let Some(value) = self.read_celsius(&mut failed_requests)? else {
+                return Ok(false);
+            };
+            let tick = Tick {
+                remaining: Some(until - now),
+                paused: false,
+                ends_at: timer.unix_secs(until),
+                started_at: timer.started_at(),
+            };
+            // Still watched for a stuck heater
+            if self.faulted(value, value, 0.0, 0.0)? {
+                return Ok(true);
+            }
+            self.cooling(FanSpeed::Max)?;
+            state.try_send(self.state(value, phases, tick))?;
+            Ok(value <= cool_down.below)
+        })?;
+        Ok(timer)
+    }
+
+    /// Holds the cycle while the timer is paused: heater off, fan left running.
+    /// Reports whether the cycle has to end on a latched fault
+    fn pause(&mut self, target: f32, value: f32, phases: &mut PhaseMachine, now: Duration) -> Result<bool, Error> {
+        self.controller.reset();
+        if self.faulted(target, value, 0.0, 0.0)? {
+            return Ok(true);
+        }
+        self.power_off()?;
+        phases.enter(Phase::Paused, now)?;
+        Ok(false)
+    }
+
+    pub fn stop(&mut self) -> anyhow::Result<(), Error> {
+        self.power_off()?;
+        self.set_fan(FanSpeed::Off)
+    }
+
+    fn read_celsius(&mut self, failed_requests: &mut u32) -> Result<Option<f32>, Error> {
+        if *failed_requests > 30 {
+            Err(anyhow!("too many failed temperature requests"))?
+        }
+        self.element_temperature = self.element.as_mut().and_then(|s| s.read_celsius().ok());
+        self.ambient_temperature = self.ambient.as_mut().and_then(|s| s.read_celsius().ok());
+        self.humidity = self.humidity_sensor.as_mut().and_then(|s| s.read_humidity().ok());
+        self.fan_rpm = self.tachometer.as_mut().and_then(|t| t.rpm().ok());
+        let result = self.sensor.read_celsius();
+        // Conversions for the next tick run while this one sleeps, a failed start falls back to a blocking read
+        self.sensor.start_conversion().ok();
+        for sensor in [self.element.as_mut(), self.ambient.as_mut()].into_iter().flatten() {
+            sensor.start_conversion().ok();
+        }
+        match result {
+            Ok(value) => {
+                *failed_requests = 0;
+                Ok(Some(value))
+            }
+            _ => {
+                *failed_requests += 1;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Runs one cycle as `session`, its id goes out with every state.
+    /// Returns the gains found by an autotune cycle, for the firmware to keep
+    pub fn run(&mut self, session: Uuid, cycle: Cycle, state: Sender<State>) -> Result<Option<Gains>, Error> {
+        self.session = Some(session);
+        let result = match cycle {
+            Cycle::Dry(profile, timer) => self.start(&profile, timer, state).map(|_| None),
+            Cycle::Autotune(timer, target) => self.autotune(target, timer, state),
+            Cycle::Program(program, timer) => self.run_program(&program, timer, state).map(|_| None),
+            Cycle::Storage(storage, timer) => self.store(&storage, timer, state).map(|_| None),
+        };
+        self.session = None;
+        result
+    }
+
+    /// Runs the relay experiment and applies the found gains,
+    /// returns `None` if it was stopped or timed out before the oscillation settled
+    pub fn autotune<K: Clock>(&mut self, target: f32, timer: SyncTimer<K>, state: Sender<State>) -> Result<Option<Gains>, Error> {
+        let mut failed_requests = 0;
+        let mut relay = RelayAutotune::new(target, autotune::CYCLES);
+        let mut phases = PhaseMachine::new(timer.now());
+        self.runaway.reset();
+        phases.enter(Phase::Autotuning, timer.now())?;
+        timer.next_sec_until(|tick| {
+            if let Some(value) = self.read_celsius(&mut failed_requests)? {
+                if tick.paused {
+                    // The oscillation is broken by a pause, the experiment starts over on resume
+                    relay = RelayAutotune::new(target, autotune::CYCLES);
+                    let faulted = self.pause(target, value, &mut phases, timer.now())?;
+                    state.try_send(self.state(value, &phases, tick).with_target(target).with_autotune(relay.progress()))?;
+                    return Ok(faulted);
+                }
+                phases.enter(Phase::Autotuning, timer.now())?;
+                let output = relay.update(value, TICK);
+                let duty = self.heat(output, FanSpeed::Middle)?;
+                if self.faulted(target, value, output, duty)? {
+                    return Ok(true);
+                }
+                state.try_send(self.state(value, &phases, tick).with_target(target).with_autotune(relay.progress()))?;
+            }
+            Ok(relay.is_done())
+        })?;
+        let gains = relay.gains();
+        if let Some(gains) = gains {
+            self.controller.tune(gains);
+        }
+        let timer = self.cool_down(timer, gains.is_some(), &mut phases, &state)?;
+        let last = self.finish(&mut phases, gains.is_some(), &timer)?;
+        state.try_send(last.with_autotune(relay.progress()))?;
+        Ok(gains)
+    }
+
+    pub fn run_program<K: Clock>(&mut self, program: &Program, timer: SyncTimer<K>, state: Sender<State>) -> Result<(), Error> {
+        let mut failed_requests = 0;
+        let mut index = 0;
+        let mut setpoint: Option<f32> = None;
+        let mut held = Duration::ZERO;
+        let mut reached = false;
+        let mut phases = PhaseMachine::new(timer.now());
+        self.controller.reset();
+        self.runaway.reset();
+        phases.enter(Phase::Preheating, timer.now())?;
+        let completed = timer.next_sec_until(|tick| {
+            let step = &program.steps[index];
+            let Some(value) = self.read_celsius(&mut failed_requests)? else {
+                return Ok(false);
+            };
+            if tick.paused {
+                // Ramp and hold stand still while paused
+                let faulted = self.pause(setpoint.unwrap_or(value), value, &mut phases, timer.now())?;
+                state.try_send(self.state(value, &phases, tick).with_target(step.target_temperature).with_step(index))?;
+                return Ok(faulted);
+            }
+            let target = step.target_temperature;
+            let current = step.ramp(setpoint.unwrap_or(value), TICK);
+            setpoint = Some(current);
+            if current == target && (value - target).abs() <= program::TOLERANCE {
+                reached = true;
+            }
+            let output = self.controller.output(current, value, TICK);
+            let (phase, duty) = if value > step.target_temperature + 10.0 {
+                self.cooling(FanSpeed::Max)?;
+                (Phase::Cooling, 0.0)
+            } else {
+                let duty = self.heat(output, step.fan)?;
+                let phase = match (reached, value > target) {
+                    (true, _) => Phase::Drying,
+                    (false, false) => Phase::Preheating,
+                    (false, true) => Phase::Cooling,
+                };
+                (phase, duty)
+            };
+            if self.faulted(current, value, output, duty)? {
+                return Ok(true);
+            }
+            phases.enter(phase, timer.now())?;
+            state.try_send(self.state(value, &phases, tick).with_target(target).with_step(index))?;
+            if reached {
+                held += TICK;
+            }
+            if reached && held >= Duration::from_secs(step.hold) {
+                index += 1;
+                held = Duration::ZERO;
+                reached = false;
+            }
+            Ok(index == program.steps.len())
+        })?;
+        // Running out of the time allowance before the last step ends is not a finished program
+        let completed = completed && index == program.steps.len();
+        let timer = self.cool_down(timer, completed, &mut phases, &state)?;
+        state.try_send(self.finish(&mut phases, completed, &timer)?)?;
+        Ok(())
+    }
+
+    pub fn start<K: Clock>(&mut self, profile: &Profile, timer: SyncTimer<K>, state: Sender<State>) -> Result<(), Error> {
+        let mut failed_requests = 0;
+        let mut profile = profile.clone();
+        let mut dryness = profile.until.map(DrynessDetector::new);
+        if dryness.is_some() && self.humidity_sensor.is_none() {
+            warn!("no humidity sensor, the cycle ends after {:?}", profile.duration);
+        }
+        let mut phases = PhaseMachine::new(timer.now());
+        self.controller.reset();
+        self.runaway.reset();
+        // A change requested before this cycle was meant for the previous one
+        self.setpoint.as_ref().and_then(|s| s.take());
+        phases.enter(Phase::Preheating, timer.now())?;
+        let completed = timer.next_sec_until(|tick| {
+            if let Some(change) = self.setpoint.as_ref().and_then(|s| s.take()) {
+                info!("target {}°C -> {}°C", profile.target_temperature, change.temperature);
+                profile.retarget(change.temperature, change.hysteresis);
+            }
+            if let Some(value) = self.read_celsius(&mut failed_requests)? {
+                let target = profile.target_temperature;
+                let min = target - profile.hysteresis;
+                let max = profile.max_temperature;
+                if tick.paused {
+                    if let Some(dryness) = dryness.as_mut() {
+                        dryness.reset();
+                    }
+                    let faulted = self.pause(target, value, &mut phases, timer.now())?;
+                    state.try_send(self.state(value, &phases, tick).with_target(target))?;
+                    return Ok(faulted);
+                }
+                let output = self.controller.output(target, value, TICK);
+                // Once dried at target, drying goes on until the chamber falls below the hysteresis band
+                let phase = match phases.current() {
+                    _ if value > max => Phase::Cooling,
+                    Phase::Drying if value >= min => Phase::Drying,
+                    _ if value >= target => Phase::Drying,
+                    _ => Phase::Preheating,
+                };
+                let duty = match phase {
+                    Phase::Cooling => {
+                        self.cooling(profile.fan.cooling)?;
+                        0.0
+                    }
+                    Phase::Drying => self.heat(output, profile.fan.dry)?,
+                    _ => self.heat(output, profile.fan.heat)?,
+                };
+                if self.faulted(target, value, output, duty)? {
+                    return Ok(true);
+                }
+                phases.enter(phase, timer.now())?;
+                state.try_send(self.state(value, &phases, tick).with_target(target))?;
+                // Humidity only tells about the material once the chamber holds the target,
+                // leaving it starts the detector over
+                match (dryness.as_mut(), self.humidity, phase) {
+                    (Some(dryness), Some(humidity), Phase::Drying) => {
+                        if dryness.update(humidity.relative, TICK) {
+                            info!("dry at {:.1}% RH", humidity.relative);
+                            return Ok(true);
+                        }
+                    }
+                    (Some(dryness), _, phase) if phase != Phase::Drying => dryness.reset(),
+                    _ => {}
+                }
+            }
+            Ok(false)
+        })?;
+        let timer = self.cool_down(timer, completed, &mut phases, &state)?;
+        state.try_send(self.finish(&mut phases, completed, &timer)?)?;
+        Ok(())
+    }
+
+    /// Keeps the chamber dry until stopped. The heater gently holds the storage target while a bound is crossed,
+    /// telemetry goes out every `report_every` and on phase changes
+    pub fn store<K: Clock>(&mut self, storage: &Storage, timer: SyncTimer<K>, state: Sender<State>) -> Result<(), Error> {
+        let mut failed_requests = 0;
+        let mut heating = false;
+        let mut heated_at: Option<Duration> = None;
+        let mut reported: Option<Duration> = None;
+        let mut phases = PhaseMachine::new(timer.now());
+        self.controller.reset();
+        self.runaway.reset();
+        phases.enter(Phase::Storing, timer.now())?;
+        let completed = timer.next_sec_until(|tick| {
+            let Some(value) = self.read_celsius(&mut failed_requests)? else {
+                return Ok(false);
+            };
+            let target = storage.target_temperature;
+            let previous = phases.current();
+            let now = timer.now();
+            let faulted = if !tick.paused && storage.needs_heat(heating, value, self.humidity.map(|h| h.relative)) {
+                if !heating {
+                    self.runaway.reset();
+                    heating = true;
+                }
+                let output = self.controller.output(target, value, TICK).min(storage.max_output);
+                let duty = self.heat(output, storage.fan)?;
+                // The detector watches for a rise under full output, gentle heating is scaled up to it
+                if self.faulted(target, value, output, duty / storage.max_output.max(f32::EPSILON))? {
+                    return Ok(true);
+                }
+                heated_at = Some(now);
+                phases.enter(Phase::Drying, now)?;
+                false
+            } else {
+                // Right after a heating burst a stuck heater shows up as a rise while off. Hours of idling only see
+                // ambient drift that the check would take for one, the absolute limit of the safety monitor still applies
+                heating = false;
+                self.controller.reset();
+                let watched = heated_at.is_some_and(|at| now.saturating_sub(at) < storage::OFF_WATCH);
+                if watched && self.faulted(target, value, 0.0, 0.0)? {
+                    return Ok(true);
+                }
+                self.power_off()?;
+                if tick.paused {
+                    phases.enter(Phase::Paused, now)?;
+                } else {
+                    self.set_fan(FanSpeed::Off)?;
+                    phases.enter(Phase::Storing, now)?;
+                }
+                self.faults.get().is_some()
+            };
+            let due = reported.is_none_or(|at| now.saturating_sub(at) >= storage.report_every());
+            if due || previous != phases.current() || faulted {
+                reported = Some(now);
+                state.try_send(self.state(value, &phases, tick).with_target(target))?;
+            }
+            Ok(faulted)
+        })?;
+        state.try_send(self.finish(&mut phases, completed, &timer)?)?;
+        Ok(())
+    }
+}
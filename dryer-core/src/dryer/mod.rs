@@ -0,0 +1,254 @@
+use serde::{Serialize};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use crate::dryer::controller::autotune::Progress;
+use crate::dryer::phase::Phase;
+use crate::dryer::profile::Profile;
+use crate::dryer::program::Program;
+use crate::dryer::safety::Fault;
+use crate::dryer::storage::Storage;
+use crate::dryer::sensor::humidity::Humidity;
+use crate::mqtt::MqttMessage;
+use crate::time::timer::SyncTimer;
+use uuid::Uuid;
+
+pub mod sensor;
+pub mod fan;
+pub mod heater;
+pub mod controller;
+pub mod power;
+pub mod profile;
+pub mod program;
+pub mod safety;
+pub mod phase;
+pub mod setpoint;
+pub mod dryness;
+pub mod storage;
+pub mod cooldown;
+pub mod session;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+
+pub enum Cycle {
+    Dry(Profile, SyncTimer),
+    Autotune(SyncTimer, f32),
+    Program(Program, SyncTimer),
+    Storage(Storage, SyncTimer),
+}
+
+/// What a session runs, known before its `Cycle` is built
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleKind {
+    Dry,
+    Autotune,
+    Program,
+    Storage,
+}
+
+impl Display for CycleKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CycleKind::Dry => write!(f, "drying"),
+            CycleKind::Autotune => write!(f, "autotune"),
+            CycleKind::Program => write!(f, "program"),
+            CycleKind::Storage => write!(f, "storage"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct State {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<Uuid>,
+    temp: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    element_temp: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ambient_temp: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    humidity: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dew_point: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fan_rpm: Option<u32>,
+    phase: Phase,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    phase_entered: Option<u64>,
+    rejected_samples: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    autotune: Option<Progress>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    step: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remaining_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ends_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    started_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fault: Option<Fault>,
+}
+
+impl State {
+    pub fn new(active: bool, temp: f32) -> Self {
+        Self { active, session: None, temp: tenths(temp), target: None, element_temp: None, ambient_temp: None, humidity: None, dew_point: None, fan_rpm: None, phase: Phase::Idle, phase_entered: None, rejected_samples: 0, autotune: None, step: None, remaining_secs: None, ends_at: None, started_at: None, fault: None }
+    }
+
+    pub fn active() -> Self {
+        Self::new(true, 0.0)
+    }
+
+    pub fn inactive() -> Self {
+        Self::new(false, 0.0)
+    }
+
+    pub fn with_session(mut self, session: Option<Uuid>) -> Self {
+        self.session = session;
+        self
+    }
+
+    /// `entered` is the time since the cycle started, published in whole seconds
+    pub fn with_phase(mut self, phase: Phase, entered: Duration) -> Self {
+        self.phase = phase;
+        self.phase_entered = Some(entered.as_secs());
+        self
+    }
+
+    pub fn with_target(mut self, target: f32) -> Self {
+        self.target = Some(tenths(target));
+        self
+    }
+
+    pub fn with_temperatures(mut self, element: Option<f32>, ambient: Option<f32>) -> Self {
+        self.element_temp = element.map(tenths);
+        self.ambient_temp = ambient.map(tenths);
+        self
+    }
+
+    pub fn with_humidity(mut self, humidity: Option<Humidity>) -> Self {
+        self.humidity = humidity.map(|h| tenths(h.relative));
+        self.dew_point = humidity.map(|h| tenths(h.dew_point()));
+        self
+    }
+
+    pub fn with_fan_rpm(mut self, rpm: Option<f32>) -> Self {
+        self.fan_rpm = rpm.map(|rpm| rpm.round() as u32);
+        self
+    }
+
+    pub fn with_rejected_samples(mut self, rejected_samples: u32) -> Self {
+        self.rejected_samples = rejected_samples;
+        self
+    }
+
+    pub fn with_autotune(mut self, progress: Progress) -> Self {
+        self.autotune = Some(progress);
+        self
+    }
+
+    pub fn with_step(mut self, index: usize) -> Self {
+        self.step = Some(index);
+        self
+    }
+
+    /// Time left in the cycle, it doesn't run down while paused.
+    /// `ends_at` is the Unix time the cycle ends at this pace, known once the wall clock is set
+    pub fn with_remaining(mut self, remaining: Option<Duration>, ends_at: Option<u64>) -> Self {
+        self.remaining_secs = remaining.map(|r| r.as_secs());
+        self.ends_at = ends_at;
+        self
+    }
+
+    /// Unix time the cycle started at, from the same origin as `ends_at`
+    pub fn with_started_at(mut self, started_at: Option<u64>) -> Self {
+        self.started_at = started_at;
+        self
+    }
+
+    pub fn with_fault(mut self, fault: Option<Fault>) -> Self {
+        if fault.is_some() {
+            self.phase = Phase::Fault;
+        }
+        self.fault = fault;
+        self
+    }
+}
+
+/// Temperatures are published with tenths of a degree
+fn tenths(value: f32) -> f32 {
+    (value * 10.0).round() / 10.0
+}
+
+impl MqttMessage for State {
+    fn to_string(&self) -> Result<String, anyhow::Error> {
+        Ok(serde_json::to_string(&self)?)
+    }
+
+    fn topic(&self) -> &str {
+        "/state"
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inactive_state_skips_empty_fields() {
+        assert_eq!(
+            State::inactive().to_string().unwrap(),
+            r#"{"active":false,"temp":0.0,"phase":"idle","rejected_samples":0}"#
+        );
+    }
+
+    #[test]
+    fn running_state_shape() {
+        let state = State::new(true, 64.96)
+            .with_target(65.0)
+            .with_temperatures(Some(181.04), None)
+            .with_humidity(Some(Humidity { relative: 12.34, temperature: 64.0 }))
+            .with_fan_rpm(Some(1499.6))
+            .with_phase(Phase::Drying, Duration::from_millis(1_800_900))
+            .with_remaining(Some(Duration::from_secs(5400)), Some(1_760_007_200))
+            .with_started_at(Some(1_760_000_000));
+        let json: serde_json::Value = serde_json::from_str(&state.to_string().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "active": true,
+                "temp": 65.0,
+                "target": 65.0,
+                "element_temp": 181.0,
+                "humidity": 12.3,
+                "dew_point": json["dew_point"],
+                "fan_rpm": 1500,
+                "phase": "drying",
+                "phase_entered": 1800,
+                "rejected_samples": 0,
+                "remaining_secs": 5400,
+                "ends_at": 1_760_007_200u64,
+                "started_at": 1_760_000_000u64,
+            })
+        );
+        let dew_point = json["dew_point"].as_f64().unwrap();
+        assert_eq!(dew_point, (dew_point * 10.0).round() / 10.0);
+    }
+
+    #[test]
+    fn temperatures_are_rounded_to_tenths() {
+        assert_eq!(tenths(23.44), 23.4);
+        assert_eq!(tenths(23.45), 23.5);
+        assert_eq!(tenths(-0.04), 0.0);
+        assert_eq!(State::new(true, 23.456).to_string().unwrap(), r#"{"active":true,"temp":23.5,"phase":"idle","rejected_samples":0}"#);
+    }
+
+    #[test]
+    fn fault_overrides_phase() {
+        let state = State::active().with_phase(Phase::Drying, Duration::ZERO).with_fault(Some(Fault::OverTemperature));
+        let json: serde_json::Value = serde_json::from_str(&state.to_string().unwrap()).unwrap();
+        assert_eq!(json["phase"], "fault");
+        assert_eq!(json["fault"], "over_temperature");
+    }
+}
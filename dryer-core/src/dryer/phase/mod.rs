@@ -0,0 +1,70 @@
+use anyhow::{anyhow, Error};
+use serde::Serialize;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Idle,
+    Preheating,
+    Drying,
+    Cooling,
+    Autotuning,
+    /// Storage mode with the heater off, waiting for a bound to be crossed
+    Storing,
+    Paused,
+    /// Heater off and fan at max after a completed cycle
+    CoolDown,
+    Finished,
+    Fault,
+}
+
+impl Phase {
+    fn allows(self, next: Phase) -> bool {
+        use Phase::*;
+        match (self, next) {
+            (Finished | Fault, _) => false,
+            (Idle, Preheating | Autotuning | Storing) => true,
+            (Preheating | Drying | Cooling, Preheating | Drying | Cooling) => true,
+            (Storing, Drying) | (Drying, Storing) => true,
+            (Preheating | Drying | Cooling | Autotuning | Storing, Paused) => true,
+            (Paused, Preheating | Drying | Cooling | Autotuning | Storing) => true,
+            (Preheating | Drying | Cooling | Autotuning | Storing | Paused, CoolDown) => true,
+            (_, Idle | Finished | Fault) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Phase of the running cycle and the moment it was entered, by the cycle's clock
+pub struct PhaseMachine {
+    current: Phase,
+    entered: Duration,
+}
+
+impl PhaseMachine {
+    pub fn new(now: Duration) -> Self {
+        Self { current: Phase::Idle, entered: now }
+    }
+
+    pub fn current(&self) -> Phase {
+        self.current
+    }
+
+    pub fn entered(&self) -> Duration {
+        self.entered
+    }
+
+    /// Moves to `next`, staying in the current phase keeps its entry time
+    pub fn enter(&mut self, next: Phase, now: Duration) -> Result<(), Error> {
+        if next == self.current {
+            return Ok(());
+        }
+        if !self.current.allows(next) {
+            return Err(anyhow!("invalid phase transition {:?} -> {:?}", self.current, next));
+        }
+        self.current = next;
+        self.entered = now;
+        Ok(())
+    }
+}
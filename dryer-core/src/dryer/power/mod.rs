@@ -0,0 +1,161 @@
+use anyhow::{anyhow, Error};
+use embedded_hal::digital::OutputPin;
+use std::time::Duration;
+
+/// Slow PWM for SSR-driven elements: within each `window` the pin is high for `duty * window`.
+/// The pin never switches faster than `min_switch`, shorter pulses are merged into full on or off.
+pub struct TimeProportioned<P: OutputPin> {
+    pin: P,
+    window: Duration,
+    min_switch: Duration,
+    duty: f32,
+    position: Duration,
+    since_switch: Duration,
+    on: bool,
+}
+
+impl<P: OutputPin> TimeProportioned<P> {
+    pub fn new(pin: P, window: Duration, min_switch: Duration) -> Self {
+        Self {
+            pin,
+            window,
+            min_switch,
+            duty: 0.0,
+            position: Duration::ZERO,
+            since_switch: min_switch,
+            on: false,
+        }
+    }
+
+    pub fn set_duty(&mut self, duty: f32) {
+        self.duty = duty.clamp(0.0, 1.0);
+    }
+
+    /// Switches the pin for the current position in the window and advances it by `dt`
+    pub fn update(&mut self, dt: Duration) -> Result<(), Error> {
+        let on_time = self.on_time();
+        let wanted = self.position < on_time;
+        if wanted != self.on && self.since_switch >= self.min_switch {
+            self.switch(wanted)?;
+        }
+        self.since_switch += dt;
+        self.position += dt;
+        if self.position >= self.window {
+            self.position = Duration::ZERO;
+        }
+        Ok(())
+    }
+
+    /// Cuts the output immediately, ignoring the minimum switch time
+    pub fn off(&mut self) -> Result<(), Error> {
+        self.duty = 0.0;
+        self.position = Duration::ZERO;
+        self.switch(false)
+    }
+
+    fn on_time(&self) -> Duration {
+        let on_time = self.window.mul_f32(self.duty);
+        if on_time < self.min_switch {
+            Duration::ZERO
+        } else if self.window.saturating_sub(on_time) < self.min_switch {
+            self.window
+        } else {
+            on_time
+        }
+    }
+
+    fn switch(&mut self, on: bool) -> Result<(), Error> {
+        if on {
+            self.pin.set_high().map_err(|e| anyhow!("power on: {:?}", e))?;
+        } else {
+            self.pin.set_low().map_err(|e| anyhow!("power off: {:?}", e))?;
+        }
+        if on != self.on {
+            self.since_switch = Duration::ZERO;
+        }
+        self.on = on;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::digital::ErrorType;
+    use std::cell::Cell;
+    use std::convert::Infallible;
+    use std::rc::Rc;
+
+    /// Keeps the level where the test can see it after the pin is moved into the driver
+    #[derive(Clone, Default)]
+    struct Pin(Rc<Cell<bool>>);
+
+    impl ErrorType for Pin {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Pin {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.set(false);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.set(true);
+            Ok(())
+        }
+    }
+
+    fn levels(duty: f32, steps: usize) -> Vec<bool> {
+        let pin = Pin::default();
+        let mut power = TimeProportioned::new(pin.clone(), Duration::from_secs(5), Duration::from_secs(1));
+        power.set_duty(duty);
+        (0..steps)
+            .map(|_| {
+                power.update(Duration::from_secs(1)).unwrap();
+                pin.0.get()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn duty_sets_on_time_within_window() {
+        assert_eq!(levels(0.6, 10), [true, true, true, false, false, true, true, true, false, false]);
+    }
+
+    #[test]
+    fn pulses_shorter_than_min_switch_are_merged() {
+        assert!(levels(0.1, 10).iter().all(|on| !on));
+        assert!(levels(0.9, 10).iter().all(|on| *on));
+    }
+
+    #[test]
+    fn holds_level_for_min_switch() {
+        let pin = Pin::default();
+        let step = Duration::from_millis(100);
+        let mut power = TimeProportioned::new(pin.clone(), Duration::from_secs(5), Duration::from_secs(1));
+        power.set_duty(1.0);
+        power.update(step).unwrap();
+        assert!(pin.0.get());
+
+        power.set_duty(0.0);
+        for _ in 0..9 {
+            power.update(step).unwrap();
+            assert!(pin.0.get());
+        }
+        power.update(step).unwrap();
+        assert!(!pin.0.get());
+    }
+
+    #[test]
+    fn off_ignores_min_switch() {
+        let pin = Pin::default();
+        let mut power = TimeProportioned::new(pin.clone(), Duration::from_secs(5), Duration::from_secs(1));
+        power.set_duty(1.0);
+        power.update(Duration::from_millis(100)).unwrap();
+        power.off().unwrap();
+        assert!(!pin.0.get());
+        power.update(Duration::from_millis(100)).unwrap();
+        assert!(!pin.0.get());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::dryer::dryness::HumidityEnd;
+use crate::dryer::heater::FanSpeed;
+use crate::dryer::setpoint::Setpoint;
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Farthest the cooling threshold may sit above the target, the material profiles use 10°C
+const MAX_ABOVE_TARGET: f32 = 15.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FanSpeeds {
+    pub heat: FanSpeed,
+    pub dry: FanSpeed,
+    pub cooling: FanSpeed,
+}
+
+impl Default for FanSpeeds {
+    fn default() -> Self {
+        Self {
+            heat: FanSpeed::Low,
+            dry: FanSpeed::Middle,
+            cooling: FanSpeed::Max,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub target_temperature: f32,
+    pub duration: Duration,
+    /// How far below the target the chamber may drop before preheating starts again
+    pub hysteresis: f32,
+    /// Above this the heater is cut and the chamber is cooled
+    pub max_temperature: f32,
+    pub fan: FanSpeeds,
+    /// Ends the cycle early once the chamber air is dry, `duration` is then the cap
+    pub until: Option<HumidityEnd>,
+}
+
+impl Profile {
+    fn material(name: &str, target_temperature: f32, hours: u64, max_temperature: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            target_temperature,
+            duration: Duration::from_secs(hours * 60 * 60),
+            hysteresis: 5.0,
+            max_temperature,
+            fan: FanSpeeds::default(),
+            until: None,
+        }
+    }
+
+    pub fn custom(target_temperature: f32, duration: Duration) -> Self {
+        Self {
+            name: "custom".to_string(),
+            target_temperature,
+            duration,
+            hysteresis: 5.0,
+            max_temperature: target_temperature + 10.0,
+            fan: FanSpeeds::default(),
+            until: None,
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "pla" => Some(Self::material("pla", 45.0, 4, 50.0)),
+            "petg" => Some(Self::material("petg", 65.0, 4, 75.0)),
+            "abs" => Some(Self::material("abs", 80.0, 4, 90.0)),
+            "asa" => Some(Self::material("asa", 80.0, 4, 90.0)),
+            "nylon" => Some(Self::material("nylon", 75.0, 12, 85.0)),
+            "tpu" => Some(Self::material("tpu", 50.0, 4, 60.0)),
+            "pc" => Some(Self::material("pc", 80.0, 6, 90.0)),
+            "silica_gel" => Some(Self::material("silica_gel", 70.0, 3, 80.0)),
+            _ => None,
+        }
+    }
+
+    /// Moves the target, keeping the cooling threshold the same distance above it
+    pub fn retarget(&mut self, target: f32, hysteresis: Option<f32>) {
+        self.max_temperature += target - self.target_temperature;
+        self.target_temperature = target;
+        if let Some(hysteresis) = hysteresis {
+            self.hysteresis = hysteresis;
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FanOverrides {
+    heat: Option<FanSpeed>,
+    dry: Option<FanSpeed>,
+    cooling: Option<FanSpeed>,
+}
+
+/// `/start` payload: a named profile and/or explicit values overriding it
+#[derive(Debug, Deserialize)]
+pub struct StartOptions {
+    profile: Option<String>,
+    duration: Option<u64>,
+    target_temperature: Option<f32>,
+    hysteresis: Option<f32>,
+    max_temperature: Option<f32>,
+    #[serde(default)]
+    fan: FanOverrides,
+    until: Option<HumidityEnd>,
+}
+
+impl StartOptions {
+    /// Without a profile name the options apply on top of a custom profile at `default_target`.
+    /// The target and hysteresis are limited by `setpoint`, as for `/set_target`
+    pub fn resolve(self, default_target: f32, setpoint: &Setpoint) -> Result<Profile, Error> {
+        let mut profile = match self.profile {
+            Some(name) => Profile::by_name(&name).ok_or_else(|| anyhow!("unknown profile: {}", name))?,
+            None => {
+                let duration = self
+                    .duration
+                    .ok_or_else(|| anyhow!("duration is required without a profile"))?;
+                let target = self.target_temperature.unwrap_or(default_target);
+                Profile::custom(target, Duration::from_secs(duration))
+            }
+        };
+        if let Some(duration) = self.duration {
+            profile.duration = Duration::from_secs(duration);
+        }
+        if let Some(target) = self.target_temperature {
+            profile.target_temperature = target;
+        }
+        if let Some(hysteresis) = self.hysteresis {
+            profile.hysteresis = setpoint.hysteresis(hysteresis);
+        }
+        if let Some(max) = self.max_temperature {
+            profile.max_temperature = max;
+        }
+        profile.fan.heat = self.fan.heat.unwrap_or(profile.fan.heat);
+        profile.fan.dry = self.fan.dry.unwrap_or(profile.fan.dry);
+        profile.fan.cooling = self.fan.cooling.unwrap_or(profile.fan.cooling);
+        if let Some(until) = self.until {
+            until.validate()?;
+            profile.until = Some(until);
+        }
+        profile.retarget(setpoint.clamp(profile.target_temperature), None);
+        if profile.max_temperature <= profile.target_temperature {
+            Err(anyhow!(
+                "max temperature {} must be above target {}",
+                profile.max_temperature,
+                profile.target_temperature
+            ))?
+        }
+        if profile.max_temperature > profile.target_temperature + MAX_ABOVE_TARGET {
+            Err(anyhow!(
+                "max temperature {} is more than {}°C above target {}",
+                profile.max_temperature,
+                MAX_ABOVE_TARGET,
+                profile.target_temperature
+            ))?
+        }
+        Ok(profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(body: &str) -> Result<Profile, Error> {
+        let options: StartOptions = serde_json::from_str(body).unwrap();
+        options.resolve(28.0, &Setpoint::new(20.0, 90.0))
+    }
+
+    #[test]
+    fn overrides_named_profile() {
+        let profile = resolve(r#"{"profile": "PETG", "duration": 600, "hysteresis": 3, "fan": {"dry": "max"}}"#).unwrap();
+        assert_eq!((profile.target_temperature, profile.hysteresis, profile.max_temperature), (65.0, 3.0, 75.0));
+        assert_eq!(profile.duration, Duration::from_secs(600));
+        assert_eq!(profile.fan.dry, FanSpeed::Max);
+    }
+
+    #[test]
+    fn clamps_target_and_keeps_cooling_margin() {
+        let profile = resolve(r#"{"duration": 600, "target_temperature": 120}"#).unwrap();
+        assert_eq!((profile.target_temperature, profile.max_temperature), (90.0, 100.0));
+    }
+
+    #[test]
+    fn negative_hysteresis_is_clamped_like_set_target() {
+        assert_eq!(resolve(r#"{"profile": "pla", "hysteresis": -5}"#).unwrap().hysteresis, 0.0);
+    }
+
+    #[test]
+    fn bounds_max_temperature() {
+        assert!(resolve(r#"{"profile": "pla", "max_temperature": 45}"#).is_err());
+        assert!(resolve(r#"{"profile": "pla", "max_temperature": 60}"#).is_ok());
+        assert!(resolve(r#"{"profile": "pla", "max_temperature": 500}"#).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_profile_and_missing_duration() {
+        assert!(resolve(r#"{"profile": "wood"}"#).is_err());
+        assert!(resolve(r#"{"target_temperature": 50}"#).is_err());
+    }
+}
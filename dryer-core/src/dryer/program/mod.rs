@@ -0,0 +1,111 @@
+use crate::dryer::fan::FanPresets;
+use crate::dryer::heater::FanSpeed;
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// A step counts as reached once the chamber is within this many degrees of its target
+pub const TOLERANCE: f32 = 2.0;
+/// Time a step may spend getting to its target on top of the ramp itself
+const STEP_ALLOWANCE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Step {
+    pub target_temperature: f32,
+    /// Degrees per minute the setpoint moves towards the target, 0 jumps straight to it
+    #[serde(default)]
+    pub ramp: f32,
+    /// Seconds to hold the target once it is reached
+    pub hold: u64,
+    pub fan: FanSpeed,
+}
+
+impl Step {
+    /// Moves `setpoint` towards the target by one tick of the ramp
+    pub fn ramp(&self, setpoint: f32, dt: Duration) -> f32 {
+        let target = self.target_temperature;
+        if self.ramp <= 0.0 {
+            return target;
+        }
+        let step = self.ramp / 60.0 * dt.as_secs_f32();
+        if setpoint < target {
+            (setpoint + step).min(target)
+        } else {
+            (setpoint - step).max(target)
+        }
+    }
+}
+
+/// Steps run in order, e.g. a pre-dry, a soak and a forced cool-down
+#[derive(Debug, Clone, Deserialize)]
+pub struct Program {
+    pub steps: Vec<Step>,
+}
+
+impl Program {
+    /// Every step heats towards its target, so it needs airflow by the interlock
+    pub fn validate(&self, presets: &FanPresets) -> Result<(), Error> {
+        if self.steps.is_empty() {
+            Err(anyhow!("program has no steps"))?
+        }
+        if let Some(step) = self.steps.iter().find(|s| s.ramp < 0.0) {
+            Err(anyhow!("negative ramp {} to {}", step.ramp, step.target_temperature))?
+        }
+        if let Some(step) = self.steps.iter().find(|s| presets.percent(s.fan) <= 0.0) {
+            Err(anyhow!("fan off in the step to {}, the heater never turns on without airflow", step.target_temperature))?
+        }
+        Ok(())
+    }
+
+    /// Safety cap for the whole program: holds, ramps and an allowance per step to settle
+    pub fn max_duration(&self) -> Duration {
+        let mut from: Option<f32> = None;
+        self.steps.iter().fold(Duration::ZERO, |total, step| {
+            let ramp = match (from, step.ramp > 0.0) {
+                (Some(from), true) => {
+                    let delta = (step.target_temperature - from).abs();
+                    Duration::from_secs_f32(delta / step.ramp * 60.0)
+                }
+                _ => Duration::ZERO,
+            };
+            from = Some(step.target_temperature);
+            total + ramp + Duration::from_secs(step.hold) + STEP_ALLOWANCE
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(body: &str) -> Program {
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn accepts_steps_with_airflow() {
+        let program = program(r#"{"steps": [{"target_temperature": 50, "ramp": 2, "hold": 600, "fan": "low"}]}"#);
+        assert!(program.validate(&FanPresets::default()).is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_program_and_negative_ramp() {
+        assert!(program(r#"{"steps": []}"#).validate(&FanPresets::default()).is_err());
+        let program = program(r#"{"steps": [{"target_temperature": 50, "ramp": -1, "hold": 0, "fan": "max"}]}"#);
+        assert!(program.validate(&FanPresets::default()).is_err());
+    }
+
+    #[test]
+    fn rejects_steps_without_airflow() {
+        for fan in [r#""off""#, r#"{"percent": 0}"#] {
+            let body = format!(
+                r#"{{"steps": [{{"target_temperature": 50, "hold": 0, "fan": "max"}}, {{"target_temperature": 65, "hold": 600, "fan": {}}}]}}"#,
+                fan
+            );
+            assert!(program(&body).validate(&FanPresets::default()).is_err(), "{}", fan);
+        }
+        // A preset configured to 0 is off as well
+        let program = program(r#"{"steps": [{"target_temperature": 50, "hold": 0, "fan": "low"}]}"#);
+        assert!(program.validate(&FanPresets::new(0.0, 50.0, 100.0)).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+pub mod monitor;
+
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    /// Heater driven for the whole watch period without the expected rise
+    HeatingStalled,
+    /// Temperature kept rising long after the heater was switched off
+    RisingWhileOff,
+    /// Chamber above the absolute maximum of the safety monitor
+    OverTemperature,
+    /// Heater on without fresh readings, e.g. the control thread died
+    SensorStale,
+    /// Fan tach below the minimum speed while the heater is on
+    FanStalled,
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fault::HeatingStalled => write!(f, "thermal runaway: heating stalled"),
+            Fault::RisingWhileOff => write!(f, "thermal runaway: rising while heater is off"),
+            Fault::OverTemperature => write!(f, "over temperature"),
+            Fault::SensorStale => write!(f, "heater on without fresh temperature readings"),
+            Fault::FanStalled => write!(f, "fan stalled while heating"),
+        }
+    }
+}
+
+/// Fault shared between threads, stays set until explicitly reset
+#[derive(Clone, Default)]
+pub struct FaultLatch {
+    fault: Arc<Mutex<Option<Fault>>>,
+}
+
+impl FaultLatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps the first fault if the latch is already tripped
+    pub fn trip(&self, fault: Fault) {
+        let mut current = self.fault.lock().unwrap_or_else(|e| e.into_inner());
+        current.get_or_insert(fault);
+    }
+
+    pub fn get(&self) -> Option<Fault> {
+        *self.fault.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn reset(&self) {
+        *self.fault.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// Thermal runaway protection in the spirit of 3D printer firmware
+pub struct RunawayDetector {
+    heating_period: Duration,
+    min_rise: f32,
+    off_grace: Duration,
+    max_off_rise: f32,
+    heating: Option<(Duration, f32)>,
+    off: Option<(Duration, f32)>,
+}
+
+impl RunawayDetector {
+    /// `min_rise` degrees are expected within every `heating_period` while the heater is driven below target,
+    /// after `off_grace` with the heater off the temperature may rise by no more than `max_off_rise`
+    pub fn new(heating_period: Duration, min_rise: f32, off_grace: Duration, max_off_rise: f32) -> Self {
+        Self {
+            heating_period,
+            min_rise,
+            off_grace,
+            max_off_rise,
+            heating: None,
+            off: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.heating = None;
+        self.off = None;
+    }
+
+    pub fn check(&mut self, target: f32, value: f32, output: f32, dt: Duration) -> Result<(), Fault> {
+        if output >= 0.5 && value < target - self.min_rise {
+            let (elapsed, start) = self.heating.get_or_insert((Duration::ZERO, value));
+            *elapsed += dt;
+            if value >= *start + self.min_rise {
+                self.heating = Some((Duration::ZERO, value));
+            } else if *elapsed >= self.heating_period {
+                return Err(Fault::HeatingStalled);
+            }
+        } else {
+            self.heating = None;
+        }
+
+        if output <= 0.0 {
+            let (elapsed, peak) = self.off.get_or_insert((Duration::ZERO, value));
+            *elapsed += dt;
+            if *elapsed <= self.off_grace {
+                *peak = peak.max(value);
+            } else if value > *peak + self.max_off_rise {
+                return Err(Fault::RisingWhileOff);
+            }
+        } else {
+            self.off = None;
+        }
+        Ok(())
+    }
+}
+
+/// Trips when the fan doesn't turn while the heater is on, the likeliest way for the chamber to overheat
+pub struct StallDetector {
+    min_rpm: f32,
+    grace: Duration,
+    stalled_for: Duration,
+}
+
+impl StallDetector {
+    /// `grace` covers the spin-up from standstill
+    pub fn new(min_rpm: f32, grace: Duration) -> Self {
+        Self { min_rpm, grace, stalled_for: Duration::ZERO }
+    }
+
+    pub fn min_rpm(&self) -> f32 {
+        self.min_rpm
+    }
+
+    /// `heating` means the heater is on with the fan commanded to run, an unreadable tach counts as stalled
+    pub fn check(&mut self, heating: bool, rpm: Option<f32>, dt: Duration) -> Result<(), Fault> {
+        if !heating || rpm.is_some_and(|rpm| rpm >= self.min_rpm) {
+            self.stalled_for = Duration::ZERO;
+            return Ok(());
+        }
+        self.stalled_for += dt;
+        if self.stalled_for >= self.grace {
+            return Err(Fault::FanStalled);
+        }
+        Ok(())
+    }
+}
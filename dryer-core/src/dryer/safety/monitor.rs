@@ -0,0 +1,202 @@
+use crate::dryer::heater::TempSensor;
+use crate::dryer::safety::{Fault, FaultLatch};
+use anyhow::Error;
+use embedded_hal::digital::{ErrorKind, ErrorType, OutputPin};
+use log::error;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Latest chamber reading, shared with the monitor
+#[derive(Clone, Default)]
+pub struct Readings {
+    last: Arc<Mutex<Option<(f32, Instant)>>>,
+}
+
+impl Readings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, value: f32) {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner()) = Some((value, Instant::now()));
+    }
+
+    pub fn latest(&self) -> Option<(f32, Instant)> {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn watch<S: TempSensor>(&self, sensor: S) -> Watched<S> {
+        Watched {
+            sensor,
+            readings: self.clone(),
+        }
+    }
+}
+
+/// Sensor that reports every successful reading to the monitor
+pub struct Watched<S: TempSensor> {
+    sensor: S,
+    readings: Readings,
+}
+
+impl<S: TempSensor> TempSensor for Watched<S> {
+    fn start_conversion(&mut self) -> Result<(), Error> {
+        self.sensor.start_conversion()
+    }
+
+    fn read_celsius(&mut self) -> Result<f32, Error> {
+        let value = self.sensor.read_celsius()?;
+        self.readings.record(value);
+        Ok(value)
+    }
+
+    fn rejected_samples(&self) -> u32 {
+        self.sensor.rejected_samples()
+    }
+}
+
+#[derive(Debug)]
+pub enum GuardedPinError<E> {
+    Fault(Fault),
+    Pin(E),
+}
+
+impl<E: Debug> Display for GuardedPinError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GuardedPinError::Fault(fault) => write!(f, "refused by latched fault: {}", fault),
+            GuardedPinError::Pin(e) => write!(f, "{:?}", e),
+        }
+    }
+}
+
+impl<E: Debug> embedded_hal::digital::Error for GuardedPinError<E> {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// Heater pin shared by the control loop and the monitor, it can't be driven high while a fault is latched
+pub struct GuardedPin<P: OutputPin> {
+    pin: Arc<Mutex<P>>,
+    on: Arc<AtomicBool>,
+    faults: FaultLatch,
+}
+
+impl<P: OutputPin> Clone for GuardedPin<P> {
+    fn clone(&self) -> Self {
+        Self {
+            pin: self.pin.clone(),
+            on: self.on.clone(),
+            faults: self.faults.clone(),
+        }
+    }
+}
+
+impl<P: OutputPin> GuardedPin<P> {
+    pub fn new(pin: P, faults: FaultLatch) -> Self {
+        Self {
+            pin: Arc::new(Mutex::new(pin)),
+            on: Arc::new(AtomicBool::new(false)),
+            faults,
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on.load(Ordering::SeqCst)
+    }
+
+    /// Pulls the pin low even if the control thread panicked while holding it
+    pub fn force_low(&self) -> Result<(), P::Error> {
+        let mut pin = self.pin.lock().unwrap_or_else(|e| e.into_inner());
+        self.on.store(false, Ordering::SeqCst);
+        pin.set_low()
+    }
+}
+
+impl<P: OutputPin> ErrorType for GuardedPin<P> {
+    type Error = GuardedPinError<P::Error>;
+}
+
+impl<P: OutputPin> OutputPin for GuardedPin<P> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.force_low().map_err(GuardedPinError::Pin)
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let mut pin = self.pin.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(fault) = self.faults.get() {
+            pin.set_low().map_err(GuardedPinError::Pin)?;
+            return Err(GuardedPinError::Fault(fault));
+        }
+        pin.set_high().map_err(GuardedPinError::Pin)?;
+        self.on.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Over-temperature cutoff running in its own thread, independent of the control loop
+pub struct SafetyMonitor<P: OutputPin> {
+    pin: GuardedPin<P>,
+    readings: Readings,
+    faults: FaultLatch,
+    max_temperature: f32,
+    stale_after: Duration,
+}
+
+impl<P: OutputPin> SafetyMonitor<P> {
+    /// Trips above `max_temperature`, or when the heater is on and no reading arrived for `stale_after`
+    pub fn new(
+        pin: GuardedPin<P>,
+        readings: Readings,
+        faults: FaultLatch,
+        max_temperature: f32,
+        stale_after: Duration,
+    ) -> Self {
+        Self {
+            pin,
+            readings,
+            faults,
+            max_temperature,
+            stale_after,
+        }
+    }
+
+    pub fn run(self) -> ! {
+        let started = Instant::now();
+        loop {
+            if let Some(fault) = self.check(started) {
+                if self.faults.get().is_none() {
+                    error!("safety monitor: {}", fault);
+                }
+                self.faults.trip(fault);
+            }
+            if self.faults.get().is_some() {
+                if let Err(e) = self.pin.force_low() {
+                    error!("safety monitor: power off: {:?}", e);
+                }
+            }
+            sleep(CHECK_INTERVAL);
+        }
+    }
+
+    fn check(&self, started: Instant) -> Option<Fault> {
+        let latest = self.readings.latest();
+        let fresh = latest.filter(|(_, at)| at.elapsed() <= self.stale_after);
+        if let Some((value, _)) = fresh {
+            if value > self.max_temperature {
+                return Some(Fault::OverTemperature);
+            }
+        }
+        let since = latest.map(|(_, at)| at).unwrap_or(started);
+        if self.pin.is_on() && fresh.is_none() && since.elapsed() > self.stale_after {
+            return Some(Fault::SensorStale);
+        }
+        None
+    }
+}
@@ -0,0 +1,161 @@
+use crate::dryer::heater::HumiditySensor;
+use crate::dryer::sensor::humidity::Humidity;
+use anyhow::{anyhow, Error};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+const CHIP_ID: u8 = 0x60;
+const REG_CHIP_ID: u8 = 0xD0;
+const REG_CALIB_T: u8 = 0x88;
+const REG_CALIB_H1: u8 = 0xA1;
+const REG_CALIB_H2: u8 = 0xE1;
+const REG_CTRL_HUM: u8 = 0xF2;
+const REG_CTRL_MEAS: u8 = 0xF4;
+const REG_TEMP: u8 = 0xFA;
+/// Humidity oversampling x1
+const CTRL_HUM: u8 = 0x01;
+/// Temperature and pressure oversampling x1, forced mode
+const CTRL_MEAS: u8 = 0x25;
+/// Maximum conversion time with x1 oversampling everywhere
+const MEASURE_MS: u32 = 10;
+
+/// Factory trimming, pressure coefficients are not needed here
+struct Calibration {
+    t1: f32,
+    t2: f32,
+    t3: f32,
+    h1: f32,
+    h2: f32,
+    h3: f32,
+    h4: f32,
+    h5: f32,
+    h6: f32,
+}
+
+/// Bosch BME280 in forced mode, a measurement is triggered on every read
+pub struct Bme280<I: I2c, D: DelayNs> {
+    i2c: I,
+    delay: D,
+    address: u8,
+    calibration: Calibration,
+}
+
+impl<I: I2c, D: DelayNs> Bme280<I, D> {
+    /// SDO pulled low, 0x77 with SDO high
+    pub const ADDRESS: u8 = 0x76;
+
+    pub fn new(mut i2c: I, delay: D, address: u8) -> Result<Self, Error> {
+        let mut id = [0u8; 1];
+        read(&mut i2c, address, REG_CHIP_ID, &mut id)?;
+        if id[0] != CHIP_ID {
+            Err(anyhow!("bme280: unexpected chip id {:#04x}", id[0]))?
+        }
+        let mut t = [0u8; 6];
+        let mut h1 = [0u8; 1];
+        let mut h = [0u8; 7];
+        read(&mut i2c, address, REG_CALIB_T, &mut t)?;
+        read(&mut i2c, address, REG_CALIB_H1, &mut h1)?;
+        read(&mut i2c, address, REG_CALIB_H2, &mut h)?;
+        let calibration = Calibration {
+            t1: u16::from_le_bytes([t[0], t[1]]) as f32,
+            t2: i16::from_le_bytes([t[2], t[3]]) as f32,
+            t3: i16::from_le_bytes([t[4], t[5]]) as f32,
+            h1: h1[0] as f32,
+            h2: i16::from_le_bytes([h[0], h[1]]) as f32,
+            h3: h[2] as f32,
+            // 12 bit values sharing the nibbles of 0xE5
+            h4: (((h[3] as i8 as i16) << 4) | (h[4] & 0x0F) as i16) as f32,
+            h5: (((h[5] as i8 as i16) << 4) | (h[4] >> 4) as i16) as f32,
+            h6: h[6] as i8 as f32,
+        };
+        Ok(Self { i2c, delay, address, calibration })
+    }
+}
+
+impl<I: I2c, D: DelayNs> HumiditySensor for Bme280<I, D> {
+    fn read_humidity(&mut self) -> Result<Humidity, Error> {
+        self.i2c.write(self.address, &[REG_CTRL_HUM, CTRL_HUM]).map_err(|e| anyhow!("bme280: {:?}", e))?;
+        self.i2c.write(self.address, &[REG_CTRL_MEAS, CTRL_MEAS]).map_err(|e| anyhow!("bme280: {:?}", e))?;
+        self.delay.delay_ms(MEASURE_MS);
+        let mut data = [0u8; 5];
+        read(&mut self.i2c, self.address, REG_TEMP, &mut data)?;
+        let adc_t = (((data[0] as u32) << 12) | ((data[1] as u32) << 4) | ((data[2] as u32) >> 4)) as f32;
+        let adc_h = u16::from_be_bytes([data[3], data[4]]) as f32;
+        let c = &self.calibration;
+        // Floating point compensation from the datasheet
+        let var1 = (adc_t / 16384.0 - c.t1 / 1024.0) * c.t2;
+        let var2 = (adc_t / 131072.0 - c.t1 / 8192.0).powi(2) * c.t3;
+        let t_fine = var1 + var2;
+        let h = t_fine - 76800.0;
+        let h = (adc_h - (c.h4 * 64.0 + c.h5 / 16384.0 * h))
+            * (c.h2 / 65536.0 * (1.0 + c.h6 / 67108864.0 * h * (1.0 + c.h3 / 67108864.0 * h)));
+        let h = h * (1.0 - c.h1 * h / 524288.0);
+        Ok(Humidity {
+            relative: h.clamp(0.0, 100.0),
+            temperature: t_fine / 5120.0,
+        })
+    }
+}
+
+fn read<I: I2c>(i2c: &mut I, address: u8, register: u8, buffer: &mut [u8]) -> Result<(), Error> {
+    i2c.write_read(address, &[register], buffer).map_err(|e| anyhow!("bme280: {:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    const ADDRESS: u8 = Bme280::<Mock, NoopDelay>::ADDRESS;
+    /// dig_T1..T3 from the datasheet compensation example
+    const CALIB_T: [u8; 6] = [0x70, 0x6B, 0x43, 0x67, 0x18, 0xFC];
+
+    /// H1 = 75, H2 = 362, H3 = 0, H4/H5/H6 packed from `calib_h`
+    fn init(calib_h: [u8; 7]) -> Vec<Transaction> {
+        vec![
+            Transaction::write_read(ADDRESS, vec![REG_CHIP_ID], vec![CHIP_ID]),
+            Transaction::write_read(ADDRESS, vec![REG_CALIB_T], CALIB_T.to_vec()),
+            Transaction::write_read(ADDRESS, vec![REG_CALIB_H1], vec![75]),
+            Transaction::write_read(ADDRESS, vec![REG_CALIB_H2], calib_h.to_vec()),
+        ]
+    }
+
+    #[test]
+    fn rejects_unknown_chip_id() {
+        // BMP280 answers 0x58 and has no humidity
+        let mut i2c = Mock::new(&[Transaction::write_read(ADDRESS, vec![REG_CHIP_ID], vec![0x58])]);
+        assert!(Bme280::new(i2c.clone(), NoopDelay::new(), ADDRESS).is_err());
+        i2c.done();
+    }
+
+    #[test]
+    fn unpacks_shared_nibbles() {
+        let mut i2c = Mock::new(&init([0x6A, 0x01, 0x00, 0x13, 0x29, 0x03, 0x1E]));
+        let c = Bme280::new(i2c.clone(), NoopDelay::new(), ADDRESS).unwrap().calibration;
+        assert_eq!((c.h1, c.h2, c.h3, c.h4, c.h5, c.h6), (75.0, 362.0, 0.0, 313.0, 50.0, 30.0));
+        i2c.done();
+
+        let mut i2c = Mock::new(&init([0x6A, 0x01, 0x00, 0xFF, 0x4B, 0xED, 0xF6]));
+        let c = Bme280::new(i2c.clone(), NoopDelay::new(), ADDRESS).unwrap().calibration;
+        assert_eq!((c.h4, c.h5, c.h6), (-5.0, -300.0, -10.0));
+        i2c.done();
+    }
+
+    #[test]
+    fn compensates_forced_measurement() {
+        let mut expectations = init([0x6A, 0x01, 0x00, 0x13, 0x29, 0x03, 0x1E]);
+        expectations.extend([
+            Transaction::write(ADDRESS, vec![REG_CTRL_HUM, CTRL_HUM]),
+            Transaction::write(ADDRESS, vec![REG_CTRL_MEAS, CTRL_MEAS]),
+            // adc_T = 519888 as in the datasheet example, adc_H = 30000
+            Transaction::write_read(ADDRESS, vec![REG_TEMP], vec![0x7E, 0xED, 0x00, 0x75, 0x30]),
+        ]);
+        let mut i2c = Mock::new(&expectations);
+        let humidity = Bme280::new(i2c.clone(), NoopDelay::new(), ADDRESS).unwrap().read_humidity().unwrap();
+        i2c.done();
+        // 25.08°C per the datasheet, 55.0% from its 32 bit integer humidity formula
+        assert!((humidity.temperature - 25.08).abs() < 0.01, "{:?}", humidity);
+        assert!((humidity.relative - 55.0).abs() < 0.1, "{:?}", humidity);
+    }
+}
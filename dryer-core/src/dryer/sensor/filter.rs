@@ -0,0 +1,187 @@
+use crate::dryer::heater::TempSensor;
+use anyhow::{anyhow, Error};
+use std::collections::VecDeque;
+
+/// DS18B20 reports this value after a power-on reset, before the first conversion
+const POWER_ON_RESET: f32 = 85.0;
+/// Largest plausible change between two consecutive samples
+const MAX_STEP: f32 = 5.0;
+/// After this many rate rejections in a row the new level is accepted as real
+const MAX_REJECTED_IN_ROW: u32 = 10;
+const MEDIAN_WINDOW: usize = 5;
+const EMA_ALPHA: f32 = 0.5;
+
+/// Plausibility layer for any `TempSensor`. Failed reads (e.g. a scratchpad CRC mismatch in the driver),
+/// the power-on reset value and implausible jumps are rejected, accepted samples pass a median and an EMA filter.
+pub struct FilteredSensor<S: TempSensor> {
+    sensor: S,
+    window: VecDeque<f32>,
+    ema: Option<f32>,
+    last: Option<f32>,
+    rejected: u32,
+    rejected_in_row: u32,
+}
+
+impl<S: TempSensor> FilteredSensor<S> {
+    pub fn new(sensor: S) -> Self {
+        Self {
+            sensor,
+            window: VecDeque::with_capacity(MEDIAN_WINDOW),
+            ema: None,
+            last: None,
+            rejected: 0,
+            rejected_in_row: 0,
+        }
+    }
+
+    fn reject(&mut self, reason: Error) -> Error {
+        self.rejected += 1;
+        reason
+    }
+
+    fn check(&self, value: f32) -> Result<(), Error> {
+        if !value.is_finite() {
+            Err(anyhow!("not a number: {}", value))?
+        }
+        if self.last.is_none() && value == POWER_ON_RESET {
+            Err(anyhow!("power-on reset value {}", value))?
+        }
+        Ok(())
+    }
+
+    fn median(&self) -> f32 {
+        let mut sorted: Vec<f32> = self.window.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        sorted[sorted.len() / 2]
+    }
+}
+
+impl<S: TempSensor> TempSensor for FilteredSensor<S> {
+    fn start_conversion(&mut self) -> Result<(), Error> {
+        self.sensor.start_conversion()
+    }
+
+    fn read_celsius(&mut self) -> Result<f32, Error> {
+        let value = match self.sensor.read_celsius() {
+            Ok(value) => value,
+            Err(e) => return Err(self.reject(e)),
+        };
+        if let Err(e) = self.check(value) {
+            return Err(self.reject(e));
+        }
+        if let Some(last) = self.last.filter(|last| (value - last).abs() > MAX_STEP) {
+            // Only rate rejections count towards accepting the new level, failed reads say nothing about it
+            if self.rejected_in_row < MAX_REJECTED_IN_ROW {
+                self.rejected_in_row += 1;
+                return Err(self.reject(anyhow!("implausible step from {} to {}", last, value)));
+            }
+            // A level change that persisted, old samples no longer describe the chamber
+            self.window.clear();
+            self.ema = None;
+        }
+        self.rejected_in_row = 0;
+        self.last = Some(value);
+        if self.window.len() == MEDIAN_WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(value);
+        let median = self.median();
+        let ema = match self.ema {
+            Some(ema) => EMA_ALPHA * median + (1.0 - EMA_ALPHA) * ema,
+            None => median,
+        };
+        self.ema = Some(ema);
+        Ok(ema)
+    }
+
+    fn rejected_samples(&self) -> u32 {
+        self.rejected + self.sensor.rejected_samples()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replays readings, `None` is a failed read
+    struct Script {
+        readings: VecDeque<Option<f32>>,
+        rejected: u32,
+    }
+
+    impl TempSensor for Script {
+        fn read_celsius(&mut self) -> Result<f32, Error> {
+            self.readings.pop_front().flatten().ok_or_else(|| anyhow!("crc mismatch"))
+        }
+
+        fn rejected_samples(&self) -> u32 {
+            self.rejected
+        }
+    }
+
+    fn filtered(readings: &[Option<f32>]) -> FilteredSensor<Script> {
+        FilteredSensor::new(Script { readings: readings.iter().copied().collect(), rejected: 0 })
+    }
+
+    fn read_all(sensor: &mut FilteredSensor<Script>) -> Vec<Option<f32>> {
+        let mut values = Vec::new();
+        while !sensor.sensor.readings.is_empty() {
+            values.push(sensor.read_celsius().ok());
+        }
+        values
+    }
+
+    #[test]
+    fn rejects_power_on_reset_as_first_sample() {
+        let mut sensor = filtered(&[Some(85.0), Some(84.0), Some(85.0)]);
+        assert_eq!(read_all(&mut sensor), [None, Some(84.0), Some(84.5)]);
+        assert_eq!(sensor.rejected_samples(), 1);
+    }
+
+    #[test]
+    fn rejects_single_spike() {
+        let mut sensor = filtered(&[Some(22.0), Some(40.0), Some(22.0)]);
+        assert_eq!(read_all(&mut sensor), [Some(22.0), None, Some(22.0)]);
+        assert_eq!(sensor.rejected_samples(), 1);
+    }
+
+    #[test]
+    fn accepts_persistent_step() {
+        let mut readings = vec![Some(22.0)];
+        readings.extend([Some(40.0); MAX_REJECTED_IN_ROW as usize + 1]);
+        let mut sensor = filtered(&readings);
+        let values = read_all(&mut sensor);
+        assert_eq!(values[0], Some(22.0));
+        assert!(values[1..=MAX_REJECTED_IN_ROW as usize].iter().all(Option::is_none));
+        // Old samples are dropped with the level change, the filter restarts at the new level
+        assert_eq!(values.last(), Some(&Some(40.0)));
+        assert_eq!(sensor.rejected_samples(), MAX_REJECTED_IN_ROW);
+    }
+
+    #[test]
+    fn failed_reads_do_not_count_towards_a_step() {
+        let mut readings = vec![Some(22.0)];
+        readings.extend([None; MAX_REJECTED_IN_ROW as usize]);
+        readings.push(Some(40.0));
+        let mut sensor = filtered(&readings);
+        assert_eq!(read_all(&mut sensor).last(), Some(&None));
+        assert_eq!(sensor.rejected_samples(), MAX_REJECTED_IN_ROW + 1);
+    }
+
+    #[test]
+    fn median_then_ema() {
+        let mut sensor = filtered(&[Some(20.0), Some(24.0), Some(20.0), Some(20.0), Some(24.0), Some(24.0), Some(24.0)]);
+        // Medians 20, 24, 20, 20, 20, 24, 24 smoothed by the EMA
+        assert_eq!(
+            read_all(&mut sensor),
+            [Some(20.0), Some(22.0), Some(21.0), Some(20.5), Some(20.25), Some(22.125), Some(23.0625)]
+        );
+    }
+
+    #[test]
+    fn counts_rejections_of_the_wrapped_sensor() {
+        let mut sensor = FilteredSensor::new(Script { readings: [None, Some(f32::NAN), Some(22.0)].into(), rejected: 3 });
+        read_all(&mut sensor);
+        assert_eq!(sensor.rejected_samples(), 5);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::dryer::heater::HumiditySensor;
+use crate::dryer::sensor::bme280::Bme280;
+use crate::dryer::sensor::sht::{Model, Sht};
+use anyhow::{anyhow, Error};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+/// Magnus formula coefficients over water, valid for -45..60°C
+const MAGNUS_B: f32 = 17.62;
+const MAGNUS_C: f32 = 243.12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Humidity {
+    /// Relative humidity, %
+    pub relative: f32,
+    /// Temperature measured by the humidity sensor itself, °C
+    pub temperature: f32,
+}
+
+impl Humidity {
+    pub fn dew_point(&self) -> f32 {
+        let gamma = (self.relative.max(0.1) / 100.0).ln() + MAGNUS_B * self.temperature / (MAGNUS_C + self.temperature);
+        MAGNUS_C * gamma / (MAGNUS_B - gamma)
+    }
+}
+
+/// Driver for the configured model: `sht3x`, `sht4x` or `bme280`, at its default address
+pub fn by_name<I, D>(name: &str, i2c: I, delay: D) -> Result<Box<dyn HumiditySensor>, Error>
+where
+    I: I2c + 'static,
+    D: DelayNs + 'static,
+{
+    match name.to_lowercase().as_str() {
+        "sht3x" => Ok(Box::new(Sht::new(i2c, delay, Model::Sht3x, Sht::<I, D>::ADDRESS))),
+        "sht4x" => Ok(Box::new(Sht::new(i2c, delay, Model::Sht4x, Sht::<I, D>::ADDRESS))),
+        "bme280" => Ok(Box::new(Bme280::new(i2c, delay, Bme280::<I, D>::ADDRESS)?)),
+        _ => Err(anyhow!("unknown humidity sensor: {}", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dew_point(relative: f32, temperature: f32) -> f32 {
+        Humidity { relative, temperature }.dew_point()
+    }
+
+    #[test]
+    fn saturated_air_is_at_dew_point() {
+        assert!((dew_point(100.0, 25.0) - 25.0).abs() < 0.01);
+        assert!((dew_point(100.0, -10.0) + 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn matches_reference_values() {
+        // Magnus formula with the Sensirion coefficients, worked by hand
+        assert!((dew_point(50.0, 25.0) - 13.85).abs() < 0.05, "{}", dew_point(50.0, 25.0));
+        // A PETG cycle: a dry chamber still has a dew point well above the room
+        assert!((dew_point(10.0, 65.0) - 21.22).abs() < 0.05, "{}", dew_point(10.0, 65.0));
+    }
+
+    #[test]
+    fn bone_dry_reading_stays_finite() {
+        assert!(dew_point(0.0, 25.0).is_finite());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod filter;
+pub mod humidity;
+pub mod sht;
+pub mod bme280;
@@ -0,0 +1,140 @@
+use crate::dryer::heater::HumiditySensor;
+use crate::dryer::sensor::humidity::Humidity;
+use anyhow::{anyhow, Error};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Model {
+    Sht3x,
+    Sht4x,
+}
+
+impl Model {
+    /// Single shot, high repeatability measurement and the time it takes
+    fn measure(self) -> (&'static [u8], u32) {
+        match self {
+            Model::Sht3x => (&[0x24, 0x00], 16),
+            Model::Sht4x => (&[0xFD], 10),
+        }
+    }
+
+    fn relative(self, raw: u16) -> f32 {
+        let raw = raw as f32 / 65535.0;
+        match self {
+            Model::Sht3x => 100.0 * raw,
+            Model::Sht4x => (125.0 * raw - 6.0).clamp(0.0, 100.0),
+        }
+    }
+}
+
+/// Sensirion SHT3x/SHT4x, both answer with temperature and humidity words protected by CRC-8
+pub struct Sht<I: I2c, D: DelayNs> {
+    i2c: I,
+    delay: D,
+    model: Model,
+    address: u8,
+}
+
+impl<I: I2c, D: DelayNs> Sht<I, D> {
+    pub const ADDRESS: u8 = 0x44;
+
+    pub fn new(i2c: I, delay: D, model: Model, address: u8) -> Self {
+        Self { i2c, delay, model, address }
+    }
+
+    fn word(data: &[u8]) -> Result<u16, Error> {
+        if crc8(&data[..2]) != data[2] {
+            Err(anyhow!("sht: crc mismatch"))?
+        }
+        Ok(u16::from_be_bytes([data[0], data[1]]))
+    }
+}
+
+impl<I: I2c, D: DelayNs> HumiditySensor for Sht<I, D> {
+    fn read_humidity(&mut self) -> Result<Humidity, Error> {
+        let (command, wait_ms) = self.model.measure();
+        self.i2c.write(self.address, command).map_err(|e| anyhow!("sht: {:?}", e))?;
+        self.delay.delay_ms(wait_ms);
+        let mut data = [0u8; 6];
+        self.i2c.read(self.address, &mut data).map_err(|e| anyhow!("sht: {:?}", e))?;
+        let temperature = Self::word(&data[0..3])?;
+        let relative = Self::word(&data[3..6])?;
+        Ok(Humidity {
+            relative: self.model.relative(relative),
+            temperature: -45.0 + 175.0 * temperature as f32 / 65535.0,
+        })
+    }
+}
+
+/// CRC-8 with polynomial 0x31 and initial value 0xFF, as in the datasheets
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0xFFu8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x31 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as Delay};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    type Sensor = Sht<Mock, CheckedDelay>;
+
+    /// 25.0°C and 50% RH on an SHT3x, with valid CRCs
+    const REPLY: [u8; 6] = [0x66, 0x66, 0x93, 0x80, 0x00, 0xA2];
+
+    fn read(model: Model, command: &[u8], wait_ms: u32, reply: &[u8]) -> Result<Humidity, Error> {
+        let expectations = [
+            Transaction::write(Sensor::ADDRESS, command.to_vec()),
+            Transaction::read(Sensor::ADDRESS, reply.to_vec()),
+        ];
+        let mut i2c = Mock::new(&expectations);
+        let mut delay = CheckedDelay::new(&[Delay::delay_ms(wait_ms)]);
+        let humidity = Sensor::new(i2c.clone(), delay.clone(), model, Sensor::ADDRESS).read_humidity();
+        i2c.done();
+        delay.done();
+        humidity
+    }
+
+    #[test]
+    fn crc_matches_datasheet() {
+        assert_eq!(crc8(&[0xBE, 0xEF]), 0x92);
+    }
+
+    #[test]
+    fn sht3x_conversion() {
+        let humidity = read(Model::Sht3x, &[0x24, 0x00], 16, &REPLY).unwrap();
+        assert!((humidity.temperature - 25.0).abs() < 0.01, "{:?}", humidity);
+        assert!((humidity.relative - 50.0).abs() < 0.01, "{:?}", humidity);
+    }
+
+    #[test]
+    fn sht4x_conversion() {
+        let humidity = read(Model::Sht4x, &[0xFD], 10, &REPLY).unwrap();
+        assert!((humidity.temperature - 25.0).abs() < 0.01, "{:?}", humidity);
+        assert!((humidity.relative - 56.5).abs() < 0.01, "{:?}", humidity);
+    }
+
+    #[test]
+    fn sht4x_clamps_humidity() {
+        let humidity = read(Model::Sht4x, &[0xFD], 10, &[0x66, 0x66, 0x93, 0x00, 0x00, crc8(&[0, 0])]).unwrap();
+        assert_eq!(humidity.relative, 0.0);
+    }
+
+    #[test]
+    fn rejects_bad_crc() {
+        let mut reply = REPLY;
+        reply[5] ^= 0x01;
+        assert!(read(Model::Sht3x, &[0x24, 0x00], 16, &reply).is_err());
+        let mut reply = REPLY;
+        reply[2] = 0x92;
+        assert!(read(Model::Sht3x, &[0x24, 0x00], 16, &reply).is_err());
+    }
+}
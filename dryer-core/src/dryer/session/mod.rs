@@ -0,0 +1,75 @@
+use crate::dryer::CycleKind;
+use crate::time::timer::Signal;
+use anyhow::{anyhow, Error};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use uuid::Uuid;
+
+struct Session {
+    id: Uuid,
+    kind: CycleKind,
+    signals: Sender<Signal>,
+}
+
+/// The cycle owning the heater, shared by the command handler and the heater thread.
+/// Every session gets its own signal channel, so a command sent while idle can't end the next cycle
+#[derive(Clone, Default)]
+pub struct Sessions {
+    current: Arc<Mutex<Option<Session>>>,
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claims the heater for a cycle of `kind`, refused while another one runs
+    pub fn begin(&self, kind: CycleKind) -> Result<(Uuid, Receiver<Signal>), Error> {
+        let mut current = self.lock();
+        if let Some(session) = current.as_ref() {
+            Err(anyhow!("cycle {} is running", session.id))?
+        }
+        let (signals, receiver) = unbounded();
+        let id = Uuid::new_v4();
+        *current = Some(Session { id, kind, signals });
+        Ok((id, receiver))
+    }
+
+    pub fn signal(&self, target: Option<Uuid>, signal: Signal) -> Result<Uuid, Error> {
+        let current = self.lock();
+        let session = running(&current, target)?;
+        session.signals.send(signal)?;
+        Ok(session.id)
+    }
+
+    /// Releases the heater, an id of a session that already ended is ignored
+    pub fn end(&self, id: Uuid) {
+        let mut current = self.lock();
+        if current.as_ref().is_some_and(|s| s.id == id) {
+            *current = None;
+        }
+    }
+
+    /// The running session `target` refers to, `None` meaning whichever runs, and what it runs
+    pub fn running(&self, target: Option<Uuid>) -> Result<(Uuid, CycleKind), Error> {
+        running(&self.lock(), target).map(|s| (s.id, s.kind))
+    }
+
+    pub fn current(&self) -> Option<Uuid> {
+        self.lock().as_ref().map(|s| s.id)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Session>> {
+        self.current.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn running(current: &Option<Session>, target: Option<Uuid>) -> Result<&Session, Error> {
+    match (current, target) {
+        (None, _) => Err(anyhow!("no cycle is running")),
+        (Some(session), Some(target)) if session.id != target => {
+            Err(anyhow!("session {} is not running, {} is", target, session.id))
+        }
+        (Some(session), _) => Ok(session),
+    }
+}
@@ -0,0 +1,43 @@
+use std::sync::{Arc, Mutex};
+
+/// Target change requested while a cycle runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetChange {
+    pub temperature: f32,
+    pub hysteresis: Option<f32>,
+}
+
+/// Target temperature handed from the MQTT thread to the running cycle, kept within a safe range
+#[derive(Clone)]
+pub struct Setpoint {
+    change: Arc<Mutex<Option<TargetChange>>>,
+    min: f32,
+    max: f32,
+}
+
+impl Setpoint {
+    pub fn new(min: f32, max: f32) -> Self {
+        Self { change: Arc::new(Mutex::new(None)), min, max }
+    }
+
+    pub fn clamp(&self, temperature: f32) -> f32 {
+        temperature.clamp(self.min, self.max)
+    }
+
+    pub fn hysteresis(&self, hysteresis: f32) -> f32 {
+        hysteresis.max(0.0)
+    }
+
+    /// Requests a new target, returns it after clamping
+    pub fn set(&self, temperature: f32, hysteresis: Option<f32>) -> f32 {
+        let temperature = self.clamp(temperature);
+        let hysteresis = hysteresis.map(|h| self.hysteresis(h));
+        *self.change.lock().unwrap_or_else(|e| e.into_inner()) = Some(TargetChange { temperature, hysteresis });
+        temperature
+    }
+
+    /// The change requested since the last call, if any
+    pub fn take(&self) -> Option<TargetChange> {
+        self.change.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
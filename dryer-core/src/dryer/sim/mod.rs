@@ -0,0 +1,342 @@
+//! Thermal model of the chamber for running `Heater` on the host: the simulation hands out a sensor,
+//! a heater pin and a fan backed by the same plant, and a clock whose `sleep` advances the plant,
+//! so a `SyncTimer::with_clock(rx, duration, sim.clock())` runs a whole cycle instantly.
+use crate::dryer::heater::{FanSpeedRegulator, Tachometer, TempSensor};
+use crate::time::clock::Clock;
+use anyhow::Error;
+use embedded_hal::digital::{ErrorType, OutputPin};
+use std::cell::RefCell;
+use std::convert::Infallible;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Integration step of the model
+const STEP: Duration = Duration::from_millis(100);
+
+/// Two-node model: the element heats the chamber air through convection that grows with the fan,
+/// the chamber loses heat to the ambient through the walls and the air exchange of the fan.
+#[derive(Debug, Clone, Copy)]
+pub struct PlantConfig {
+    pub ambient: f32,
+    /// Heater power, W
+    pub heater_power: f32,
+    /// Thermal mass of the element, J/°C
+    pub element_capacity: f32,
+    /// Thermal mass of the air, walls and spool, J/°C
+    pub chamber_capacity: f32,
+    /// Element to air transfer with the fan off, W/°C
+    pub transfer: f32,
+    /// Extra element to air transfer at full fan speed, W/°C
+    pub fan_transfer: f32,
+    /// Chamber to ambient losses with the fan off, W/°C
+    pub loss: f32,
+    /// Extra chamber losses at full fan speed, W/°C
+    pub fan_loss: f32,
+    /// Fan speed at full airflow
+    pub fan_max_rpm: f32,
+}
+
+impl Default for PlantConfig {
+    fn default() -> Self {
+        Self {
+            ambient: 22.0,
+            heater_power: 200.0,
+            element_capacity: 150.0,
+            chamber_capacity: 3000.0,
+            transfer: 2.0,
+            fan_transfer: 8.0,
+            loss: 3.0,
+            fan_loss: 1.0,
+            fan_max_rpm: 3000.0,
+        }
+    }
+}
+
+struct Plant {
+    config: PlantConfig,
+    element: f32,
+    chamber: f32,
+    heater_on: bool,
+    fan: f32,
+    fan_jammed: bool,
+    now: Duration,
+    samples: Vec<(Duration, f32)>,
+}
+
+impl Plant {
+    fn advance(&mut self, duration: Duration) {
+        let mut left = duration;
+        while !left.is_zero() {
+            let dt = left.min(STEP);
+            self.step(dt.as_secs_f32());
+            left -= dt;
+            self.now += dt;
+        }
+        self.samples.push((self.now, self.chamber));
+    }
+
+    /// Airflow actually moved, 0..1
+    fn airflow(&self) -> f32 {
+        if self.fan_jammed {
+            0.0
+        } else {
+            self.fan
+        }
+    }
+
+    fn step(&mut self, dt: f32) {
+        let c = self.config;
+        let fan = self.airflow();
+        let power = if self.heater_on { c.heater_power } else { 0.0 };
+        let transfer = (c.transfer + c.fan_transfer * fan) * (self.element - self.chamber);
+        let loss = (c.loss + c.fan_loss * fan) * (self.chamber - c.ambient);
+        self.element += (power - transfer) / c.element_capacity * dt;
+        self.chamber += (transfer - loss) / c.chamber_capacity * dt;
+    }
+}
+
+#[derive(Clone)]
+pub struct Simulation {
+    plant: Rc<RefCell<Plant>>,
+}
+
+impl Simulation {
+    /// Starts with the whole chamber at ambient temperature
+    pub fn new(config: PlantConfig) -> Self {
+        Self {
+            plant: Rc::new(RefCell::new(Plant {
+                config,
+                element: config.ambient,
+                chamber: config.ambient,
+                heater_on: false,
+                fan: 0.0,
+                fan_jammed: false,
+                now: Duration::ZERO,
+                samples: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn clock(&self) -> SimClock {
+        SimClock { plant: self.plant.clone() }
+    }
+
+    pub fn sensor(&self) -> SimSensor {
+        SimSensor { plant: self.plant.clone() }
+    }
+
+    pub fn heater(&self) -> SimHeater {
+        SimHeater { plant: self.plant.clone() }
+    }
+
+    pub fn fan(&self) -> SimFan {
+        SimFan { plant: self.plant.clone() }
+    }
+
+    pub fn tachometer(&self) -> SimTachometer {
+        SimTachometer { plant: self.plant.clone() }
+    }
+
+    /// Blocks the fan: no airflow and no tach pulses whatever the commanded speed
+    pub fn jam_fan(&self) {
+        self.plant.borrow_mut().fan_jammed = true;
+    }
+
+    pub fn chamber(&self) -> f32 {
+        self.plant.borrow().chamber
+    }
+
+    /// Chamber temperature after every clock sleep so far
+    pub fn response(&self) -> Response {
+        Response {
+            samples: self.plant.borrow().samples.clone(),
+        }
+    }
+}
+
+pub struct SimClock {
+    plant: Rc<RefCell<Plant>>,
+}
+
+impl Clock for SimClock {
+    fn now(&self) -> Duration {
+        self.plant.borrow().now
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.plant.borrow_mut().advance(duration);
+    }
+}
+
+pub struct SimSensor {
+    plant: Rc<RefCell<Plant>>,
+}
+
+impl TempSensor for SimSensor {
+    fn read_celsius(&mut self) -> Result<f32, Error> {
+        Ok(self.plant.borrow().chamber)
+    }
+}
+
+pub struct SimHeater {
+    plant: Rc<RefCell<Plant>>,
+}
+
+impl ErrorType for SimHeater {
+    type Error = Infallible;
+}
+
+impl OutputPin for SimHeater {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.plant.borrow_mut().heater_on = false;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.plant.borrow_mut().heater_on = true;
+        Ok(())
+    }
+}
+
+pub struct SimFan {
+    plant: Rc<RefCell<Plant>>,
+}
+
+impl FanSpeedRegulator for SimFan {
+    fn set_percent(&mut self, percent: f32) -> Result<(), Error> {
+        self.plant.borrow_mut().fan = percent.clamp(0.0, 100.0) / 100.0;
+        Ok(())
+    }
+}
+
+pub struct SimTachometer {
+    plant: Rc<RefCell<Plant>>,
+}
+
+impl Tachometer for SimTachometer {
+    fn rpm(&mut self) -> Result<f32, Error> {
+        let plant = self.plant.borrow();
+        Ok(plant.airflow() * plant.config.fan_max_rpm)
+    }
+}
+
+pub struct Response {
+    samples: Vec<(Duration, f32)>,
+}
+
+impl Response {
+    pub fn samples(&self) -> &[(Duration, f32)] {
+        &self.samples
+    }
+
+    /// Degrees the chamber peaked above `target`
+    pub fn overshoot(&self, target: f32) -> f32 {
+        self.samples
+            .iter()
+            .map(|(_, value)| value - target)
+            .fold(0.0, f32::max)
+    }
+
+    /// Time from which the chamber stays within `band` degrees of `target`, `None` if it never settles
+    pub fn settling_time(&self, target: f32, band: f32) -> Option<Duration> {
+        let outside = |(_, value): &(Duration, f32)| (value - target).abs() > band;
+        match self.samples.iter().rposition(outside) {
+            None => self.samples.first().map(|(at, _)| *at),
+            Some(last) => self.samples.get(last + 1).map(|(at, _)| *at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dryer::controller::pid::{Gains, Pid};
+    use crate::dryer::heater::{FanSpeed, Heater};
+    use crate::dryer::power::TimeProportioned;
+    use crate::dryer::profile::Profile;
+    use crate::dryer::program::{Program, Step};
+    use crate::dryer::safety::{Fault, FaultLatch, RunawayDetector, StallDetector};
+    use crate::time::timer::SyncTimer;
+
+    fn heater(sim: &Simulation, faults: FaultLatch) -> Heater<SimHeater, SimSensor, SimFan, Pid> {
+        Heater::new(
+            TimeProportioned::new(sim.heater(), Duration::from_secs(5), Duration::from_secs(1)),
+            sim.sensor(),
+            sim.fan(),
+            Pid::new(Gains::new(0.08, 0.0005, 0.8)),
+            RunawayDetector::new(Duration::from_secs(600), 2.0, Duration::from_secs(300), 5.0),
+            faults,
+        )
+    }
+
+    /// Jams the fan once the clock reaches `at`
+    struct JamAt {
+        clock: SimClock,
+        sim: Simulation,
+        at: Duration,
+    }
+
+    impl Clock for JamAt {
+        fn now(&self) -> Duration {
+            self.clock.now()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.clock.sleep(duration);
+            if self.clock.now() >= self.at {
+                self.sim.jam_fan();
+            }
+        }
+    }
+
+    #[test]
+    fn petg_settles_with_little_overshoot() {
+        let sim = Simulation::new(PlantConfig::default());
+        let faults = FaultLatch::new();
+        let (_signals, rx) = crossbeam_channel::unbounded();
+        let (states, _updates) = crossbeam_channel::unbounded();
+        let profile = Profile::by_name("petg").unwrap();
+        let timer = SyncTimer::with_clock(rx, Duration::from_secs(2 * 3600), sim.clock());
+        heater(&sim, faults.clone()).start(&profile, timer, states).unwrap();
+
+        let response = sim.response();
+        let target = profile.target_temperature;
+        assert_eq!(faults.get(), None);
+        assert!(response.overshoot(target) < 2.5, "overshoot {}", response.overshoot(target));
+        let settled = response.settling_time(target, 1.0).expect("never settled");
+        assert!(settled < Duration::from_secs(1800), "settled after {:?}", settled);
+    }
+
+    #[test]
+    fn heater_held_off_by_interlock_is_not_a_runaway() {
+        let sim = Simulation::new(PlantConfig::default());
+        let faults = FaultLatch::new();
+        let (_signals, rx) = crossbeam_channel::unbounded();
+        let (states, _updates) = crossbeam_channel::unbounded();
+        let program = Program { steps: vec![Step { target_temperature: 65.0, ramp: 0.0, hold: 600, fan: FanSpeed::Off }] };
+        let timer = SyncTimer::with_clock(rx, Duration::from_secs(1800), sim.clock());
+        heater(&sim, faults.clone()).run_program(&program, timer, states).unwrap();
+
+        assert_eq!(faults.get(), None);
+        assert!(sim.chamber() < 23.0, "chamber at {}", sim.chamber());
+    }
+
+    #[test]
+    fn jammed_fan_trips_stall_fault() {
+        let sim = Simulation::new(PlantConfig::default());
+        let faults = FaultLatch::new();
+        let (_signals, rx) = crossbeam_channel::unbounded();
+        let (states, _updates) = crossbeam_channel::unbounded();
+        let profile = Profile::by_name("petg").unwrap();
+        let at = Duration::from_secs(600);
+        let clock = JamAt { clock: sim.clock(), sim: sim.clone(), at };
+        let timer = SyncTimer::with_clock(rx, Duration::from_secs(3600), clock);
+        heater(&sim, faults.clone())
+            .with_tachometer(Box::new(sim.tachometer()), 3000.0, StallDetector::new(300.0, Duration::from_secs(5)))
+            .start(&profile, timer, states)
+            .unwrap();
+
+        assert_eq!(faults.get(), Some(Fault::FanStalled));
+        assert!(sim.clock().now() <= at + Duration::from_secs(10), "tripped at {:?}", sim.clock().now());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::dryer::heater::FanSpeed;
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// How long the rising-while-off check runs after a heating burst, longer idles see ambient drift
+pub const OFF_WATCH: Duration = Duration::from_secs(1800);
+
+/// `/storage` payload, keeps spools dry between prints.
+/// The heater stays off until the chamber is too humid or too cold
+#[derive(Debug, Clone, Deserialize)]
+pub struct Storage {
+    /// Heating starts above this RH, %
+    #[serde(default = "default_max_humidity")]
+    pub max_humidity: f32,
+    /// ... and stops once RH is this much lower
+    #[serde(default = "default_humidity_band")]
+    pub humidity_band: f32,
+    /// Heating also starts below this chamber temperature
+    #[serde(default = "default_min_temperature")]
+    pub min_temperature: f32,
+    /// ... and stops once the chamber is this much warmer
+    #[serde(default = "default_temperature_band")]
+    pub temperature_band: f32,
+    /// Chamber temperature held while heating
+    #[serde(default = "default_target_temperature")]
+    pub target_temperature: f32,
+    /// Heater output limit for gentle heating, 0..1
+    #[serde(default = "default_max_output")]
+    pub max_output: f32,
+    #[serde(default = "default_fan")]
+    pub fan: FanSpeed,
+    /// Telemetry interval, a phase change is reported right away
+    #[serde(default = "default_report_every")]
+    pub report_every: u64,
+}
+
+fn default_max_humidity() -> f32 {
+    20.0
+}
+
+fn default_humidity_band() -> f32 {
+    5.0
+}
+
+fn default_min_temperature() -> f32 {
+    15.0
+}
+
+fn default_temperature_band() -> f32 {
+    3.0
+}
+
+fn default_target_temperature() -> f32 {
+    40.0
+}
+
+fn default_max_output() -> f32 {
+    0.3
+}
+
+fn default_fan() -> FanSpeed {
+    FanSpeed::Low
+}
+
+fn default_report_every() -> u64 {
+    60
+}
+
+impl Storage {
+    pub fn validate(&self) -> Result<(), Error> {
+        if !(0.0..=1.0).contains(&self.max_output) {
+            Err(anyhow!("max_output {} is out of 0..1", self.max_output))?
+        }
+        if self.target_temperature < self.min_temperature + self.temperature_band {
+            Err(anyhow!(
+                "target {} must be at least {} to warm the chamber up",
+                self.target_temperature,
+                self.min_temperature + self.temperature_band
+            ))?
+        }
+        Ok(())
+    }
+
+    pub fn report_every(&self) -> Duration {
+        Duration::from_secs(self.report_every)
+    }
+
+    /// Whether to heat, `heating` is the current decision so both bounds have hysteresis
+    pub fn needs_heat(&self, heating: bool, temperature: f32, humidity: Option<f32>) -> bool {
+        let (temperature_limit, humidity_limit) = match heating {
+            false => (self.min_temperature, self.max_humidity),
+            true => (self.min_temperature + self.temperature_band, self.max_humidity - self.humidity_band),
+        };
+        temperature < temperature_limit || humidity.is_some_and(|rh| rh > humidity_limit)
+    }
+}
@@ -0,0 +1,4 @@
+pub mod dryer;
+pub mod mqtt;
+pub mod schedule;
+pub mod time;
@@ -0,0 +1,267 @@
+use anyhow::anyhow;
+use crate::dryer::profile::StartOptions;
+use crate::dryer::program::Program;
+use crate::dryer::storage::Storage;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use uuid::Uuid;
+
+/// Commands acting on a running cycle take an optional `session`, without it they apply to whichever runs
+#[derive(Debug)]
+pub enum Command {
+    Start(StartOptions),
+    Stop { session: Option<Uuid> },
+    Pause { session: Option<Uuid> },
+    Resume { session: Option<Uuid> },
+    Adjust { delta_secs: i64, session: Option<Uuid> },
+    SetTarget { target_temperature: f32, hysteresis: Option<f32>, session: Option<Uuid> },
+    Autotune { target: f32 },
+    Program(Program),
+    Storage(Storage),
+    Reset,
+}
+
+#[derive(Debug, Deserialize)]
+struct AutotuneOptions {
+    target: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct AdjustOptions {
+    delta_secs: i64,
+    session: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TargetOptions {
+    target_temperature: f32,
+    hysteresis: Option<f32>,
+    session: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionOptions {
+    session: Option<Uuid>,
+}
+
+/// An empty payload targets the running session
+fn session(data: &[u8]) -> Result<Option<Uuid>, anyhow::Error> {
+    if data.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    let val: SessionOptions = serde_json::from_slice(data)?;
+    Ok(val.session)
+}
+
+impl Command {
+    /// Decodes a message received on one of the command topics
+    pub fn parse(topic: &str, data: &[u8]) -> Result<Self, anyhow::Error> {
+        match topic {
+            "/start" => Ok(Command::Start(serde_json::from_slice(data)?)),
+            "/stop" => Ok(Command::Stop { session: session(data)? }),
+            "/pause" => Ok(Command::Pause { session: session(data)? }),
+            "/resume" => Ok(Command::Resume { session: session(data)? }),
+            "/adjust" => {
+                let val: AdjustOptions = serde_json::from_slice(data)?;
+                Ok(Command::Adjust { delta_secs: val.delta_secs, session: val.session })
+            }
+            "/set_target" => {
+                let val: TargetOptions = serde_json::from_slice(data)?;
+                Ok(Command::SetTarget {
+                    target_temperature: val.target_temperature,
+                    hysteresis: val.hysteresis,
+                    session: val.session,
+                })
+            }
+            "/autotune" => {
+                let val: AutotuneOptions = serde_json::from_slice(data)?;
+                Ok(Command::Autotune { target: val.target })
+            }
+            "/program" => Ok(Command::Program(serde_json::from_slice(data)?)),
+            "/storage" => Ok(Command::Storage(serde_json::from_slice(data)?)),
+            "/reset" => Ok(Command::Reset),
+            topic => Err(anyhow!("unknown topic {}", topic)),
+        }
+    }
+
+    /// Topic the command came from, without the slash
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Start(_) => "start",
+            Command::Stop { .. } => "stop",
+            Command::Pause { .. } => "pause",
+            Command::Resume { .. } => "resume",
+            Command::Adjust { .. } => "adjust",
+            Command::SetTarget { .. } => "set_target",
+            Command::Autotune { .. } => "autotune",
+            Command::Program(_) => "program",
+            Command::Storage(_) => "storage",
+            Command::Reset => "reset",
+        }
+    }
+}
+
+/// A command that was refused, e.g. `/start` while a cycle is running
+#[derive(Debug, Serialize)]
+pub struct Rejection {
+    /// Topic without the slash
+    command: String,
+    reason: String,
+    /// The running session, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<Uuid>,
+}
+
+impl Rejection {
+    pub fn new(command: &str, reason: impl Display, session: Option<Uuid>) -> Self {
+        Self { command: command.trim_start_matches('/').to_string(), reason: reason.to_string(), session }
+    }
+}
+
+impl MqttMessage for Rejection {
+    fn to_string(&self) -> Result<String, anyhow::Error> {
+        Ok(serde_json::to_string(&self)?)
+    }
+
+    fn topic(&self) -> &str {
+        "/rejected"
+    }
+}
+
+pub trait MqttMessage {
+    fn to_string(&self) -> Result<String, anyhow::Error>;
+
+    fn topic(&self) -> &str;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dryer::heater::FanSpeed;
+    use crate::dryer::setpoint::Setpoint;
+    use std::time::Duration;
+
+    const SESSION: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+    fn session_id() -> Uuid {
+        Uuid::parse_str(SESSION).unwrap()
+    }
+
+    #[test]
+    fn parses_start() {
+        let command = Command::parse("/start", br#"{"profile": "petg", "duration": 3600}"#).unwrap();
+        let Command::Start(options) = command else { panic!("{:?}", command) };
+        let profile = options.resolve(28.0, &Setpoint::new(20.0, 90.0)).unwrap();
+        assert_eq!(profile.target_temperature, 65.0);
+        assert_eq!(profile.duration, Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn start_without_profile_uses_default_target() {
+        let command = Command::parse("/start", br#"{"duration": 600}"#).unwrap();
+        let Command::Start(options) = command else { panic!("{:?}", command) };
+        assert_eq!(options.resolve(28.0, &Setpoint::new(20.0, 90.0)).unwrap().target_temperature, 28.0);
+    }
+
+    #[test]
+    fn session_commands_take_an_optional_session() {
+        let body = format!(r#"{{"session": "{}"}}"#, SESSION);
+        for topic in ["/stop", "/pause", "/resume"] {
+            let targeted = Command::parse(topic, body.as_bytes()).unwrap();
+            let any = Command::parse(topic, b" \n").unwrap();
+            match (targeted, any) {
+                (Command::Stop { session: a }, Command::Stop { session: b })
+                | (Command::Pause { session: a }, Command::Pause { session: b })
+                | (Command::Resume { session: a }, Command::Resume { session: b }) => {
+                    assert_eq!(a, Some(session_id()));
+                    assert_eq!(b, None);
+                }
+                other => panic!("{}: {:?}", topic, other),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_adjust() {
+        let command = Command::parse("/adjust", br#"{"delta_secs": -600}"#).unwrap();
+        assert!(matches!(command, Command::Adjust { delta_secs: -600, session: None }), "{:?}", command);
+    }
+
+    #[test]
+    fn parses_set_target() {
+        let body = format!(r#"{{"target_temperature": 55.5, "session": "{}"}}"#, SESSION);
+        let command = Command::parse("/set_target", body.as_bytes()).unwrap();
+        let Command::SetTarget { target_temperature, hysteresis, session } = command else { panic!("{:?}", command) };
+        assert_eq!(target_temperature, 55.5);
+        assert_eq!(hysteresis, None);
+        assert_eq!(session, Some(session_id()));
+    }
+
+    #[test]
+    fn parses_autotune() {
+        let command = Command::parse("/autotune", br#"{"target": 60}"#).unwrap();
+        assert!(matches!(command, Command::Autotune { target } if target == 60.0), "{:?}", command);
+    }
+
+    #[test]
+    fn parses_program() {
+        let body = br#"{"steps": [
+            {"target_temperature": 50, "ramp": 2, "hold": 600, "fan": "low"},
+            {"target_temperature": 65, "hold": 1800, "fan": {"percent": 65}}
+        ]}"#;
+        let Command::Program(program) = Command::parse("/program", body).unwrap() else { panic!() };
+        assert_eq!(program.steps.len(), 2);
+        assert_eq!(program.steps[0].fan, FanSpeed::Low);
+        assert_eq!(program.steps[1].ramp, 0.0);
+        assert_eq!(program.steps[1].fan, FanSpeed::Percent(65.0));
+    }
+
+    #[test]
+    fn parses_storage_with_defaults() {
+        let Command::Storage(storage) = Command::parse("/storage", br#"{"max_humidity": 20}"#).unwrap() else { panic!() };
+        assert_eq!(storage.max_humidity, 20.0);
+        assert!(storage.validate().is_ok());
+    }
+
+    #[test]
+    fn parses_reset() {
+        assert!(matches!(Command::parse("/reset", b"").unwrap(), Command::Reset));
+    }
+
+    #[test]
+    fn rejects_bad_payloads() {
+        let bad: [(&str, &[u8]); 9] = [
+            ("/start", b""),
+            ("/start", br#"{"duration": "long"}"#),
+            ("/stop", br#"{"session": "not-a-uuid"}"#),
+            ("/pause", b"pause"),
+            ("/adjust", b"{}"),
+            ("/set_target", br#"{"hysteresis": 1}"#),
+            ("/autotune", b""),
+            ("/program", br#"{"steps": [{"target_temperature": 50}]}"#),
+            ("/storage", br#"{"max_humidity": "dry"}"#),
+        ];
+        for (topic, data) in bad {
+            assert!(Command::parse(topic, data).is_err(), "{} {:?}", topic, String::from_utf8_lossy(data));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_topic() {
+        assert!(Command::parse("/explode", b"{}").is_err());
+    }
+
+    #[test]
+    fn rejection_names_the_topic() {
+        let error = Command::parse("/start", br#"{"duration": "long"}"#).unwrap_err();
+        let json: serde_json::Value = serde_json::from_str(&Rejection::new("/start", error, None).to_string().unwrap()).unwrap();
+        assert_eq!(json["command"], "start");
+        assert!(json["reason"].as_str().unwrap().contains("invalid type"), "{}", json);
+    }
+
+    #[test]
+    fn rejection_omits_missing_session() {
+        let rejection = Rejection::new("start", "already running", None);
+        assert_eq!(rejection.to_string().unwrap(), r#"{"command":"start","reason":"already running"}"#);
+    }
+}
@@ -30,6 +30,7 @@ pub trait Timer {
     fn next_sec<F: FnMut(NaiveDateTime)>(&self, cb: F);
 }
 
+#[derive(Default)]
 pub struct Scheduler {}
 
 impl Scheduler {
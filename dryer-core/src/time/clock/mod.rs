@@ -0,0 +1,49 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Wall clock readings before this (November 2023) mean it was never set
+const WALL_CLOCK_SET: u64 = 1_700_000_000;
+
+/// Source of time for timers, so control loops can run against a virtual clock on the host
+pub trait Clock {
+    /// Time since an arbitrary, fixed starting point
+    fn now(&self) -> Duration;
+
+    fn sleep(&self, duration: Duration);
+
+    /// Unix time in seconds, `None` until the wall clock has been set, e.g. by SNTP
+    fn wall_secs(&self) -> Option<u64> {
+        None
+    }
+}
+
+pub struct SystemClock {
+    started: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { started: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        sleep(duration);
+    }
+
+    fn wall_secs(&self) -> Option<u64> {
+        let secs = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs();
+        (secs >= WALL_CLOCK_SET).then_some(secs)
+    }
+}
@@ -0,0 +1,302 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+use crossbeam_channel::Receiver;
+use crate::time::clock::{Clock, SystemClock};
+
+pub const TICK: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    Stop,
+    Pause,
+    Resume,
+    /// Extends (or with a negative value shortens) the remaining time, in seconds
+    Adjust(i64),
+}
+
+/// Timer status passed to the callback on every tick
+#[derive(Debug, Clone, Copy)]
+pub struct Tick {
+    /// `None` for a timer without an end
+    pub remaining: Option<Duration>,
+    pub paused: bool,
+    /// Unix time the timer runs out at this pace, `None` until the wall clock is set
+    pub ends_at: Option<u64>,
+    /// Unix time the timer was created, from the same origin as `ends_at`
+    pub started_at: Option<u64>,
+}
+
+pub struct SyncTimer<K: Clock = SystemClock> {
+    signals: Receiver<Signal>,
+    duration: Option<Duration>,
+    clock: K,
+    created: Duration,
+    /// Unix time at `created`, taken once the wall clock is set
+    epoch: OnceLock<u64>,
+}
+
+impl SyncTimer {
+    pub fn new(signals: Receiver<Signal>, secs: Duration) -> Self {
+        Self::with_clock(signals, secs, SystemClock::new())
+    }
+
+    /// Runs until stopped
+    pub fn indefinite(signals: Receiver<Signal>) -> Self {
+        Self::indefinite_with_clock(signals, SystemClock::new())
+    }
+}
+
+impl<K: Clock> SyncTimer<K> {
+    pub fn with_clock(signals: Receiver<Signal>, secs: Duration, clock: K) -> Self {
+        Self::create(signals, Some(secs), clock)
+    }
+
+    pub fn indefinite_with_clock(signals: Receiver<Signal>, clock: K) -> Self {
+        Self::create(signals, None, clock)
+    }
+
+    fn create(signals: Receiver<Signal>, duration: Option<Duration>, clock: K) -> Self {
+        let created = clock.now();
+        let timer = Self { signals, duration, clock, created, epoch: OnceLock::new() };
+        timer.started_at();
+        timer
+    }
+
+    /// Untimed follow-up stage of the cycle on the same signals and clock, it ends on its own condition or a stop
+    pub fn next_stage(self) -> Self {
+        Self { duration: None, ..self }
+    }
+
+    /// Time since the timer was created, by its clock
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    /// Unix time of `at` by the timer's clock, `None` until the wall clock is set.
+    /// The offset between the clocks is taken once, so times within a cycle don't drift against each other
+    pub fn unix_secs(&self, at: Duration) -> Option<u64> {
+        if self.epoch.get().is_none() {
+            let since = self.clock.now().saturating_sub(self.created);
+            if let Some(wall) = self.clock.wall_secs() {
+                self.epoch.set(wall.saturating_sub(since.as_secs())).ok();
+            }
+        }
+        let epoch = *self.epoch.get()?;
+        Some(epoch + at.saturating_sub(self.created).as_secs())
+    }
+
+    /// Unix time the timer was created
+    pub fn started_at(&self) -> Option<u64> {
+        self.unix_secs(self.created)
+    }
+
+    pub fn next_sec<F: FnMut(Tick) -> Result<(), anyhow::Error>>(
+        &self,
+        mut cb: F,
+    ) -> Result<bool, anyhow::Error> {
+        self.next_sec_until(|tick| cb(tick).map(|_| false))
+    }
+
+    /// Same as `next_sec`, but stops early once the callback reports it is done.
+    /// Returns `false` if the timer was stopped before that.
+    /// Ticks keep coming while paused, but the remaining time only runs down after a resume
+    pub fn next_sec_until<F: FnMut(Tick) -> Result<bool, anyhow::Error>>(
+        &self,
+        mut cb: F,
+    ) -> Result<bool, anyhow::Error> {
+        let mut remaining = self.duration;
+        let mut paused = false;
+        loop {
+            for signal in self.signals.try_iter() {
+                match signal {
+                    Signal::Stop => return Ok(false),
+                    Signal::Pause => paused = true,
+                    Signal::Resume => paused = false,
+                    Signal::Adjust(delta) => remaining = remaining.map(|r| adjust(r, delta)),
+                }
+            }
+            if remaining.is_some_and(|r| r.is_zero()) {
+                return Ok(true)
+            }
+            let start = self.clock.now();
+            let tick = Tick {
+                remaining,
+                paused,
+                ends_at: remaining.and_then(|r| self.unix_secs(start + r)),
+                started_at: self.started_at(),
+            };
+            if cb(tick)? {
+                return Ok(true)
+            }
+            let duration = self.clock.now().saturating_sub(start);
+            self.clock.sleep(TICK.saturating_sub(duration));
+            if !paused {
+                remaining = remaining.map(|r| r.saturating_sub(TICK))
+            }
+        }
+    }
+}
+
+fn adjust(remaining: Duration, delta: i64) -> Duration {
+    if delta >= 0 {
+        remaining + Duration::from_secs(delta as u64)
+    } else {
+        remaining.saturating_sub(Duration::from_secs(delta.unsigned_abs()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const EPOCH: u64 = 1_760_000_000;
+
+    /// Time only moves when the timer sleeps, the wall clock reads `EPOCH + now` from `synced_at` on
+    #[derive(Default)]
+    struct FakeClock {
+        now: Cell<Duration>,
+        synced_at: Duration,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Duration {
+            self.now.get()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration);
+        }
+
+        fn wall_secs(&self) -> Option<u64> {
+            (self.now.get() >= self.synced_at).then(|| EPOCH + self.now.get().as_secs())
+        }
+    }
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn runs_for_its_duration() {
+        let (_tx, rx) = crossbeam_channel::unbounded();
+        let timer = SyncTimer::with_clock(rx, secs(3), FakeClock::default());
+        let mut ticks = Vec::new();
+        assert!(timer.next_sec(|tick| {
+            ticks.push((tick.remaining, tick.ends_at));
+            Ok(())
+        }).unwrap());
+        let ends_at = Some(EPOCH + 3);
+        assert_eq!(ticks, [(Some(secs(3)), ends_at), (Some(secs(2)), ends_at), (Some(secs(1)), ends_at)]);
+        assert_eq!(timer.now(), secs(3));
+    }
+
+    #[test]
+    fn end_time_waits_for_wall_clock() {
+        let (_tx, rx) = crossbeam_channel::unbounded();
+        let clock = FakeClock { now: Cell::new(secs(10)), synced_at: secs(12) };
+        let timer = SyncTimer::with_clock(rx, secs(5), clock);
+        let mut ticks = Vec::new();
+        timer
+            .next_sec(|tick| {
+                ticks.push((tick.started_at, tick.ends_at));
+                Ok(())
+            })
+            .unwrap();
+        // Once synced, both times are counted back to the timer's creation at 10s
+        let synced = (Some(EPOCH + 10), Some(EPOCH + 15));
+        assert_eq!(ticks, [(None, None), (None, None), synced, synced, synced]);
+    }
+
+    #[test]
+    fn stop_ends_early() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let timer = SyncTimer::with_clock(rx, secs(10), FakeClock::default());
+        let mut ticks = 0;
+        let finished = timer
+            .next_sec(|_| {
+                ticks += 1;
+                if ticks == 2 {
+                    tx.send(Signal::Stop)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert!(!finished);
+        assert_eq!(ticks, 2);
+    }
+
+    #[test]
+    fn pause_holds_remaining_time() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let timer = SyncTimer::with_clock(rx, secs(3), FakeClock::default());
+        let mut ticks = Vec::new();
+        timer
+            .next_sec(|tick| {
+                ticks.push((tick.remaining.unwrap().as_secs(), tick.paused, tick.ends_at.unwrap() - EPOCH));
+                match ticks.len() {
+                    1 => tx.send(Signal::Pause)?,
+                    3 => tx.send(Signal::Resume)?,
+                    _ => {}
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(ticks, [(3, false, 3), (2, true, 3), (2, true, 4), (2, false, 5), (1, false, 5)]);
+    }
+
+    #[test]
+    fn adjust_extends_and_shortens() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let timer = SyncTimer::with_clock(rx, secs(3), FakeClock::default());
+        tx.send(Signal::Adjust(2)).unwrap();
+        let mut ticks = 0;
+        assert!(timer.next_sec(|_| {
+            ticks += 1;
+            Ok(())
+        }).unwrap());
+        assert_eq!(ticks, 5);
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let timer = SyncTimer::with_clock(rx, secs(3), FakeClock::default());
+        tx.send(Signal::Adjust(-10)).unwrap();
+        let mut ticks = 0;
+        assert!(timer.next_sec(|_| {
+            ticks += 1;
+            Ok(())
+        }).unwrap());
+        assert_eq!(ticks, 0);
+    }
+
+    #[test]
+    fn adjust_does_not_end_an_indefinite_timer() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let timer = SyncTimer::indefinite_with_clock(rx, FakeClock::default()).next_stage();
+        tx.send(Signal::Adjust(-10)).unwrap();
+        let mut ticks = 0;
+        let finished = timer
+            .next_sec(|tick| {
+                assert_eq!((tick.remaining, tick.ends_at), (None, None));
+                ticks += 1;
+                if ticks == 100 {
+                    tx.send(Signal::Stop)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert!(!finished);
+        assert_eq!(ticks, 100);
+    }
+
+    #[test]
+    fn next_sec_until_stops_when_done() {
+        let (_tx, rx) = crossbeam_channel::unbounded();
+        let timer = SyncTimer::with_clock(rx, secs(60), FakeClock::default());
+        let mut ticks = 0;
+        assert!(timer.next_sec_until(|_| {
+            ticks += 1;
+            Ok(ticks == 4)
+        }).unwrap());
+        assert_eq!(ticks, 4);
+    }
+}
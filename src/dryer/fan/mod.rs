@@ -1,30 +1,31 @@
-use crate::dryer::heater::{FanSpeed, FanSpeedRegulator};
+use dryer_core::dryer::fan::FanCalibration;
+use dryer_core::dryer::heater::FanSpeedRegulator;
 use anyhow::Error;
 use esp_idf_hal::ledc::LedcDriver;
+use std::thread::sleep;
 
 pub struct Fan<'a> {
     pwm: LedcDriver<'a>,
+    calibration: FanCalibration,
+    running: bool,
 }
 
 impl<'a> Fan<'a> {
-    pub fn new(pwm: LedcDriver<'a>) -> Self {
-        Fan { pwm }
+    pub fn new(pwm: LedcDriver<'a>, calibration: FanCalibration) -> Self {
+        Fan { pwm, calibration, running: false }
     }
 }
 
 impl<'a> FanSpeedRegulator for Fan<'a> {
-    fn speed(&mut self, speed: FanSpeed) -> Result<(), Error> {
+    fn set_percent(&mut self, percent: f32) -> Result<(), Error> {
         let max = self.pwm.get_max_duty();
-        self.pwm.set_duty(match speed {
-            FanSpeed::Low => {
-                (max as f64 * 30.0 / 100.0) as u32
-            },
-            FanSpeed::Middle => {
-                (max as f64 * 50.0 / 100.0) as u32
-            },
-            FanSpeed::Max => max,
-            FanSpeed::Off => 0,
-        })?;
+        let duty = self.calibration.duty(percent);
+        if duty > 0.0 && !self.running && !self.calibration.kick.is_zero() {
+            self.pwm.set_duty(max)?;
+            sleep(self.calibration.kick);
+        }
+        self.running = duty > 0.0;
+        self.pwm.set_duty((max as f32 * duty) as u32)?;
         Ok(())
     }
 }
@@ -1 +1 @@
-pub mod temperature;
\ No newline at end of file
+pub mod temperature;
@@ -1,46 +1,156 @@
 use anyhow::{anyhow, Error};
 use esp_idf_svc::hal::delay::Ets;
-use onewire::{DeviceSearch, OneWire, OpenDrainOutput, DS18B20};
-use crate::dryer::heater::TempSensor;
+use log::info;
+use onewire::ds18b20::{self, MeasureResolution};
+use onewire::{Device, DeviceSearch, OneWire, OpenDrainOutput, DS18B20};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use dryer_core::dryer::heater::TempSensor;
 
-pub struct DS18B20Sensor<ODO: OpenDrainOutput> {
-    device: DS18B20,
-    wire: OneWire<ODO>,
+/// Factory defaults of the alarm registers, rewritten together with the configuration
+const ALARM_HIGH: u8 = 0x4B;
+const ALARM_LOW: u8 = 0x46;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Resolution {
+    Bits9,
+    Bits10,
+    Bits11,
+    Bits12,
 }
 
-impl<ODO: OpenDrainOutput> DS18B20Sensor<ODO> {
-    pub fn new(mut wire: OneWire<ODO>, device_search_attempts: i32) -> Result<Self, Error> {
+impl Resolution {
+    pub fn from_bits(bits: u8) -> Result<Self, Error> {
+        match bits {
+            9 => Ok(Resolution::Bits9),
+            10 => Ok(Resolution::Bits10),
+            11 => Ok(Resolution::Bits11),
+            12 => Ok(Resolution::Bits12),
+            _ => Err(anyhow!("unsupported DS18B20 resolution: {} bits", bits)),
+        }
+    }
+
+    fn measure(&self) -> MeasureResolution {
+        match self {
+            Resolution::Bits9 => MeasureResolution::TC8,
+            Resolution::Bits10 => MeasureResolution::TC4,
+            Resolution::Bits11 => MeasureResolution::TC2,
+            Resolution::Bits12 => MeasureResolution::TC,
+        }
+    }
+
+    fn conversion_time(&self) -> Duration {
+        Duration::from_millis(self.measure().time_ms() as u64)
+    }
+}
+
+/// All DS18B20 devices found on one OneWire bus
+pub struct DS18B20Bus<ODO: OpenDrainOutput> {
+    wire: Rc<RefCell<OneWire<ODO>>>,
+    devices: Vec<Device>,
+    resolution: Resolution,
+}
+
+impl<ODO: OpenDrainOutput> DS18B20Bus<ODO> {
+    pub fn new(mut wire: OneWire<ODO>, device_search_attempts: i32, resolution: Resolution) -> Result<Self, Error> {
         let mut delay = Ets;
-        let mut search = DeviceSearch::new();
         wire.reset(&mut delay)
             .map_err(|e| anyhow!("wire.reset: {:?}", e))?;
         for _ in 0..device_search_attempts {
-            let device = wire
+            let mut search = DeviceSearch::new_for_family(ds18b20::FAMILY_CODE);
+            let mut devices = Vec::new();
+            while let Some(device) = wire
                 .search_next(&mut search, &mut delay)
-                .map_err(|e| anyhow!("wire.search_next: {:?}", e))?;
-            if device.is_some() {
-                return Ok(DS18B20Sensor {
-                    device: DS18B20::new(device.unwrap()).map_err(|e| anyhow!("device.new: {:?}", e))?,
-                    wire
+                .map_err(|e| anyhow!("wire.search_next: {:?}", e))?
+            {
+                info!("temperature device found: {}", device);
+                devices.push(device);
+            }
+            if !devices.is_empty() {
+                for device in devices.iter() {
+                    wire.reset_select_write_only(
+                        &mut delay,
+                        device,
+                        &[ds18b20::Command::WriteScratchpad as u8, ALARM_HIGH, ALARM_LOW, resolution.measure() as u8],
+                    )
+                    .map_err(|e| anyhow!("wire.write_scratchpad {}: {:?}", device, e))?;
+                }
+                return Ok(DS18B20Bus {
+                    wire: Rc::new(RefCell::new(wire)),
+                    devices,
+                    resolution,
                 });
             }
         }
         Err(anyhow!("temperature device not found"))
     }
+
+    /// Sensor with the given ROM address (`28:ff:...`), or the first one found when the address is empty
+    pub fn sensor(&self, rom: &str) -> Result<DS18B20Sensor<ODO>, Error> {
+        let device = if rom.is_empty() {
+            self.devices[0].clone()
+        } else {
+            let address = Device::from_str(rom).map_err(|e| anyhow!("rom {}: {:?}", rom, e))?;
+            self.devices
+                .iter()
+                .find(|d| **d == address)
+                .cloned()
+                .ok_or_else(|| anyhow!("temperature device {} not found", rom))?
+        };
+        Ok(DS18B20Sensor {
+            device: DS18B20::new(device).map_err(|e| anyhow!("device.new: {:?}", e))?,
+            wire: self.wire.clone(),
+            resolution: self.resolution,
+            converting_since: None,
+        })
+    }
+
+    /// Sensor for an optional role, `None` when no address is configured
+    pub fn optional_sensor(&self, rom: &str) -> Result<Option<DS18B20Sensor<ODO>>, Error> {
+        match rom.is_empty() {
+            true => Ok(None),
+            false => self.sensor(rom).map(Some),
+        }
+    }
+}
+
+pub struct DS18B20Sensor<ODO: OpenDrainOutput> {
+    device: DS18B20,
+    wire: Rc<RefCell<OneWire<ODO>>>,
+    resolution: Resolution,
+    converting_since: Option<Instant>,
 }
 
 impl<ODO: OpenDrainOutput> TempSensor for DS18B20Sensor<ODO> {
-    fn read_celsius(&mut self) -> Result<u16, Error> {
+    fn start_conversion(&mut self) -> Result<(), Error> {
         let mut delay = Ets;
-        let resolution = self
-            .device
-            .measure_temperature(&mut self.wire, &mut delay)
+        self.device
+            .measure_temperature(&mut self.wire.borrow_mut(), &mut delay)
             .map_err(|e| anyhow!("device.measure_temperature: {:?}", e))?;
-        Ets::delay_ms(resolution.time_ms() as u32);
+        self.converting_since = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Only waits for the part of the conversion time that hasn't passed since `start_conversion`
+    fn read_celsius(&mut self) -> Result<f32, Error> {
+        if self.converting_since.is_none() {
+            self.start_conversion()?;
+        }
+        let elapsed = self.converting_since.take().map(|s| s.elapsed()).unwrap_or_default();
+        let remaining = self.resolution.conversion_time().saturating_sub(elapsed);
+        if !remaining.is_zero() {
+            sleep(remaining);
+        }
+        let mut delay = Ets;
+        let mut wire = self.wire.borrow_mut();
         let temperature = self
             .device
-            .read_temperature(&mut self.wire, &mut delay)
+            .read_temperature(&mut wire, &mut delay)
             .map_err(|e| anyhow!("device.read_temperature: {:?}", e))?;
-        Ok(temperature / 16.0 as u16)
+        // Raw value is a signed count of 1/16 °C
+        Ok(temperature as i16 as f32 / 16.0)
     }
 }
@@ -0,0 +1,55 @@
+use dryer_core::dryer::heater::Tachometer;
+use anyhow::Error;
+use esp_idf_hal::gpio::{Input, InputPin, OutputPin, PinDriver, Pull};
+use esp_idf_svc::sys::{esp, gpio_install_isr_service, gpio_intr_enable, gpio_isr_handler_add, gpio_set_intr_type};
+use esp_idf_svc::sys::{gpio_int_type_t_GPIO_INTR_NEGEDGE, ESP_ERR_INVALID_STATE};
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
+
+/// Tach pulses since the last read, the board has a single fan
+static PULSES: AtomicU32 = AtomicU32::new(0);
+
+unsafe extern "C" fn on_pulse(_: *mut c_void) {
+    PULSES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts the open collector tach output of a 4-pin fan on a falling edge interrupt,
+/// the ESP32-C3 has no PCNT unit
+pub struct Tach<'d, T: InputPin + OutputPin> {
+    _pin: PinDriver<'d, T, Input>,
+    pulses_per_rev: u32,
+    last: Instant,
+}
+
+impl<'d, T: InputPin + OutputPin> Tach<'d, T> {
+    /// Most PC fans give 2 pulses per revolution
+    pub fn new(mut pin: PinDriver<'d, T, Input>, pulses_per_rev: u32) -> Result<Self, Error> {
+        pin.set_pull(Pull::Up)?;
+        let gpio = pin.pin();
+        unsafe {
+            // The service may already be installed by another driver
+            let installed = gpio_install_isr_service(0);
+            if installed != ESP_ERR_INVALID_STATE as i32 {
+                esp!(installed)?;
+            }
+            esp!(gpio_set_intr_type(gpio, gpio_int_type_t_GPIO_INTR_NEGEDGE))?;
+            esp!(gpio_isr_handler_add(gpio, Some(on_pulse), ptr::null_mut()))?;
+            esp!(gpio_intr_enable(gpio))?;
+        }
+        Ok(Tach { _pin: pin, pulses_per_rev: pulses_per_rev.max(1), last: Instant::now() })
+    }
+}
+
+impl<'d, T: InputPin + OutputPin> Tachometer for Tach<'d, T> {
+    fn rpm(&mut self) -> Result<f32, Error> {
+        let pulses = PULSES.swap(0, Ordering::Relaxed);
+        let elapsed = self.last.elapsed().as_secs_f32();
+        self.last = Instant::now();
+        if elapsed <= 0.0 {
+            return Ok(0.0);
+        }
+        Ok(pulses as f32 / self.pulses_per_rev as f32 * 60.0 / elapsed)
+    }
+}
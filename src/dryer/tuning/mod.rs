@@ -0,0 +1,31 @@
+use dryer_core::dryer::controller::pid::Gains;
+use anyhow::Error;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+
+const NAMESPACE: &str = "dryer";
+const KEY: &str = "pid_gains";
+
+/// PID gains found by autotune, kept in NVS so they survive a reboot
+pub struct TunedGains {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl TunedGains {
+    pub fn new(partition: EspDefaultNvsPartition) -> Result<Self, Error> {
+        Ok(TunedGains { nvs: EspNvs::new(partition, NAMESPACE, true)? })
+    }
+
+    /// `None` until an autotune has finished on this unit
+    pub fn load(&self) -> Result<Option<Gains>, Error> {
+        let mut buf = [0u8; 128];
+        match self.nvs.get_str(KEY, &mut buf)? {
+            Some(json) => Ok(Some(serde_json::from_str(json)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn save(&mut self, gains: Gains) -> Result<(), Error> {
+        self.nvs.set_str(KEY, &serde_json::to_string(&gains)?)?;
+        Ok(())
+    }
+}
@@ -1,5 +1,3 @@
-#[macro_use]
-mod schedule;
 mod time;
 mod wifi;
 mod dryer;
@@ -10,22 +8,41 @@ use anyhow::Result;
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::hal::gpio::PinDriver;
 use esp_idf_svc::hal::peripherals::Peripherals;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sntp::EspSntp;
 use esp_idf_svc::wifi::EspWifi;
-use log::error;
+use log::{error, info};
 use onewire::OneWire;
-use dryer::sensor::temperature::DS18B20Sensor;
+use dryer_core::dryer::sensor::filter::FilteredSensor;
+use dryer_core::dryer::sensor::humidity;
+use dryer::sensor::temperature::{DS18B20Bus, Resolution};
 use wifi::{Connection, Credentials};
 use dotenv_codegen::dotenv;
 use embedded_svc::wifi::AuthMethod;
 use esp_idf_hal::ledc::{LedcDriver, LedcTimerDriver};
 use esp_idf_hal::ledc::config::TimerConfig;
 use esp_idf_hal::ledc::Resolution::Bits10;
+use esp_idf_hal::delay::FreeRtos;
+use esp_idf_hal::i2c::{I2cConfig, I2cDriver};
 use esp_idf_hal::units::Hertz;
 use dryer::fan::Fan;
-use dryer::heater::Heater;
-use dryer::{State};
-use mqtt::{Mqtt, Command};
-use time::timer::SyncTimer;
+use dryer::tach::Tach;
+use dryer::tuning::TunedGains;
+use dryer_core::dryer::fan::{FanCalibration, FanPresets};
+use dryer_core::dryer::heater::Heater;
+use dryer_core::dryer::controller::pid::{Gains, Pid};
+use dryer_core::dryer::power::TimeProportioned;
+use std::time::Duration;
+use dryer_core::dryer::controller::autotune;
+use dryer_core::dryer::cooldown::CoolDown;
+use dryer_core::dryer::safety::monitor::{GuardedPin, Readings, SafetyMonitor};
+use dryer_core::dryer::safety::{FaultLatch, RunawayDetector, StallDetector};
+use dryer_core::dryer::session::Sessions;
+use dryer_core::dryer::setpoint::Setpoint;
+use dryer_core::dryer::{Cycle, CycleKind, State};
+use dryer_core::mqtt::{Command, Rejection};
+use mqtt::Mqtt;
+use dryer_core::time::timer::{Signal, SyncTimer};
 use crossbeam_channel::{unbounded};
 
 fn main() -> Result<()> {
@@ -40,9 +57,36 @@ fn main() -> Result<()> {
 
 fn start() -> Result<()> {
     let peripherals = Peripherals::take()?;
-    let (timers_tx, timers_rx) = unbounded();
-    let (cancel_tx, cancel_rx) = unbounded();
+    let nvs = EspDefaultNvsPartition::take()?;
+    let (cycles_tx, cycles_rx) = unbounded();
     let (states_tx, states_rx) = unbounded();
+    let faults = FaultLatch::new();
+    let sessions = Sessions::new();
+    let heater_sessions = sessions.clone();
+    let heater_faults = faults.clone();
+    let readings = Readings::new();
+    let setpoint = Setpoint::new(
+        dotenv!("TARGET_TEMPERATURE_MIN").parse::<f32>()?,
+        dotenv!("TARGET_TEMPERATURE_MAX").parse::<f32>()?,
+    );
+    let default_target = dotenv!("TARGET_TEMPERATURE").parse::<f32>()?;
+    let fan_presets = FanPresets::new(
+        dotenv!("FAN_LOW").parse::<f32>()?,
+        dotenv!("FAN_MIDDLE").parse::<f32>()?,
+        dotenv!("FAN_MAX").parse::<f32>()?,
+    );
+    let heater_setpoint = setpoint.clone();
+    let power_pin = GuardedPin::new(
+        PinDriver::output(peripherals.pins.gpio2)?.into_output()?,
+        faults.clone(),
+    );
+    let monitor = SafetyMonitor::new(
+        power_pin.clone(),
+        readings.clone(),
+        faults.clone(),
+        dotenv!("SAFETY_MAX_TEMPERATURE").parse::<f32>()?,
+        Duration::from_secs(dotenv!("SAFETY_STALE_SECS").parse::<u64>()?),
+    );
     // Init WI-FI
     let sys_loop = EspSystemEventLoop::take()?;
     let wifi = EspWifi::new(peripherals.modem, sys_loop.clone(), None)?;
@@ -55,8 +99,11 @@ fn start() -> Result<()> {
         sys_loop,
     );
     connection.open(AuthMethod::WPA2Personal)?;
+    // Wall clock for the cycle start and end times in /state, synced in the background
+    let _sntp = EspSntp::new_default()?;
 
     let handles = vec![
+        thread::spawn(move || monitor.run()),
         thread::spawn(move || {
             // Init MQTT
             let mut mqtt = Mqtt::new(mqtt::Credentials::new(
@@ -71,15 +118,112 @@ fn start() -> Result<()> {
                 let send_state = |mqtt: &mut Mqtt, state: State| -> Result<(), anyhow::Error>{
                     mqtt.send_message(state)
                 };
-                mqtt.on_command(|mqtt, msg| {
+                mqtt.on_command(|mqtt, incoming| {
+                    let msg = match incoming {
+                        Ok(msg) => msg,
+                        Err((topic, reason)) => {
+                            error!("{} rejected: {}", topic, reason);
+                            return mqtt.send_message(Rejection::new(&topic, reason, sessions.current()));
+                        }
+                    };
+                    let command = msg.name();
+                    let reject = |mqtt: &mut Mqtt, reason: anyhow::Error| -> Result<(), anyhow::Error> {
+                        error!("{} rejected: {}", command, reason);
+                        mqtt.send_message(Rejection::new(command, reason, sessions.current()))
+                    };
+                    if let Some(fault) = faults.get() {
+                        if !matches!(msg, Command::Stop { .. } | Command::Reset) {
+                            return reject(mqtt, anyhow::anyhow!("{}, /reset first", fault));
+                        }
+                    }
                     match msg {
-                        Command::Start(d) => {
-                            send_state(mqtt, State::active())?;
-                            Ok(timers_tx.send(SyncTimer::new(cancel_rx.clone(), d))?)
+                        Command::Start(options) => {
+                            let profile = match options.resolve(default_target, &setpoint) {
+                                Ok(profile) => profile,
+                                Err(e) => return reject(mqtt, e),
+                            };
+                            let (session, signals) = match sessions.begin(CycleKind::Dry) {
+                                Ok(session) => session,
+                                Err(e) => return reject(mqtt, e),
+                            };
+                            info!("start {}: {} profile, {}°C for {:?}", session, profile.name, profile.target_temperature, profile.duration);
+                            send_state(mqtt, State::active().with_session(Some(session)))?;
+                            let timer = SyncTimer::new(signals, profile.duration);
+                            Ok(cycles_tx.send((session, Cycle::Dry(profile, timer)))?)
+                        },
+                        Command::Stop { session } => match sessions.signal(session, Signal::Stop) {
+                            Ok(session) => {
+                                info!("stop {}", session);
+                                Ok(())
+                            },
+                            Err(e) => reject(mqtt, e),
+                        },
+                        Command::Pause { session } => match sessions.signal(session, Signal::Pause) {
+                            Ok(_) => Ok(()),
+                            Err(e) => reject(mqtt, e),
+                        },
+                        Command::Resume { session } => match sessions.signal(session, Signal::Resume) {
+                            Ok(_) => Ok(()),
+                            Err(e) => reject(mqtt, e),
+                        },
+                        Command::Adjust { delta_secs, session } => match sessions.signal(session, Signal::Adjust(delta_secs)) {
+                            Ok(_) => {
+                                info!("adjust: {:+}s", delta_secs);
+                                Ok(())
+                            },
+                            Err(e) => reject(mqtt, e),
+                        },
+                        Command::SetTarget { target_temperature, hysteresis, session } => {
+                            // Only a drying cycle follows the setpoint
+                            match sessions.running(session) {
+                                Ok((_, CycleKind::Dry)) => {}
+                                Ok((_, kind)) => return reject(mqtt, anyhow::anyhow!("a {} cycle keeps its own targets", kind)),
+                                Err(e) => return reject(mqtt, e),
+                            }
+                            let target = setpoint.set(target_temperature, hysteresis);
+                            info!("set target: {}°C", target);
+                            Ok(())
+                        },
+                        Command::Autotune { target } => {
+                            let target = setpoint.clamp(target);
+                            let (session, signals) = match sessions.begin(CycleKind::Autotune) {
+                                Ok(session) => session,
+                                Err(e) => return reject(mqtt, e),
+                            };
+                            send_state(mqtt, State::active().with_session(Some(session)))?;
+                            Ok(cycles_tx.send((session, Cycle::Autotune(SyncTimer::new(signals, autotune::TIMEOUT), target)))?)
+                        },
+                        Command::Program(mut program) => {
+                            for step in program.steps.iter_mut() {
+                                step.target_temperature = setpoint.clamp(step.target_temperature);
+                            }
+                            if let Err(e) = program.validate(&fan_presets) {
+                                return reject(mqtt, e);
+                            }
+                            let (session, signals) = match sessions.begin(CycleKind::Program) {
+                                Ok(session) => session,
+                                Err(e) => return reject(mqtt, e),
+                            };
+                            send_state(mqtt, State::active().with_session(Some(session)))?;
+                            let timer = SyncTimer::new(signals, program.max_duration());
+                            Ok(cycles_tx.send((session, Cycle::Program(program, timer)))?)
+                        },
+                        Command::Storage(mut storage) => {
+                            storage.target_temperature = setpoint.clamp(storage.target_temperature);
+                            if let Err(e) = storage.validate() {
+                                return reject(mqtt, e);
+                            }
+                            let (session, signals) = match sessions.begin(CycleKind::Storage) {
+                                Ok(session) => session,
+                                Err(e) => return reject(mqtt, e),
+                            };
+                            info!("storage: heat above {}% RH or below {}°C", storage.max_humidity, storage.min_temperature);
+                            send_state(mqtt, State::active().with_session(Some(session)))?;
+                            Ok(cycles_tx.send((session, Cycle::Storage(storage, SyncTimer::indefinite(signals))))?)
                         },
-                        Command::Stop => {
-                            send_state(mqtt, State::inactive())?;
-                            Ok(cancel_tx.send(true)?)
+                        Command::Reset => {
+                            faults.reset();
+                            send_state(mqtt, State::inactive())
                         },
                     }
                 })?;
@@ -104,25 +248,115 @@ fn start() -> Result<()> {
             ).unwrap();
 
             //Init temperature sensor
-            let mut pin_driver = PinDriver::output(peripherals.pins.gpio10).unwrap().into_input_output().unwrap();
-            let wire = OneWire::new(&mut pin_driver, false);
-            let temp_sensor = DS18B20Sensor::new(wire, 100).unwrap();
+            let pin_driver = PinDriver::output(peripherals.pins.gpio10).unwrap().into_input_output().unwrap();
+            let wire = OneWire::new(pin_driver, false);
+            let resolution = Resolution::from_bits(dotenv!("DS18B20_RESOLUTION").parse::<u8>().unwrap()).unwrap();
+            let bus = DS18B20Bus::new(wire, 100, resolution).unwrap();
+            let temp_sensor = readings.watch(FilteredSensor::new(bus.sensor(dotenv!("CHAMBER_SENSOR_ROM")).unwrap()));
 
-            //Init heater
-            let power = PinDriver::output(peripherals.pins.gpio2).unwrap().into_output().unwrap();
+            //Init heater, gains from the last autotune replace the configured ones
+            let mut tuned = TunedGains::new(nvs).unwrap();
+            let gains = match tuned.load() {
+                Ok(Some(gains)) => {
+                    info!("pid gains from nvs: {:?}", gains);
+                    gains
+                }
+                saved => {
+                    if let Err(e) = saved {
+                        error!("pid gains in nvs unreadable: {}", e);
+                    }
+                    Gains::new(
+                        dotenv!("PID_KP").parse::<f32>().unwrap(),
+                        dotenv!("PID_KI").parse::<f32>().unwrap(),
+                        dotenv!("PID_KD").parse::<f32>().unwrap(),
+                    )
+                }
+            };
+            let power = TimeProportioned::new(
+                power_pin,
+                Duration::from_secs(dotenv!("HEATER_WINDOW_SECS").parse::<u64>().unwrap()),
+                Duration::from_secs(dotenv!("HEATER_MIN_SWITCH_SECS").parse::<u64>().unwrap()),
+            );
             let mut dryer = Heater::new(
                 power,
-                dotenv!("TARGET_TEMPERATURE").parse::<u16>().unwrap(),
                 temp_sensor,
-                Fan::new(pwm)
+                Fan::new(pwm, FanCalibration::new(
+                    dotenv!("FAN_MIN_DUTY").parse::<f32>().unwrap(),
+                    Duration::from_millis(dotenv!("FAN_KICK_MS").parse::<u64>().unwrap()),
+                )),
+                Pid::new(gains),
+                RunawayDetector::new(
+                    Duration::from_secs(dotenv!("RUNAWAY_HEATING_PERIOD_SECS").parse::<u64>().unwrap()),
+                    dotenv!("RUNAWAY_MIN_RISE").parse::<f32>().unwrap(),
+                    Duration::from_secs(dotenv!("RUNAWAY_OFF_GRACE_SECS").parse::<u64>().unwrap()),
+                    dotenv!("RUNAWAY_MAX_OFF_RISE").parse::<f32>().unwrap(),
+                ),
+                heater_faults.clone(),
             );
+            dryer = dryer.with_setpoint(heater_setpoint).with_fan_presets(fan_presets);
+            match dotenv!("COOL_DOWN_SECS").parse::<u64>().unwrap() {
+                0 => {}
+                secs => {
+                    dryer = dryer.with_cool_down(CoolDown::new(
+                        dotenv!("COOL_DOWN_BELOW").parse::<f32>().unwrap(),
+                        Duration::from_secs(secs),
+                    ));
+                }
+            }
+            match dotenv!("HUMIDITY_SENSOR") {
+                "" => {}
+                model => {
+                    let i2c = I2cDriver::new(
+                        peripherals.i2c0,
+                        peripherals.pins.gpio5,
+                        peripherals.pins.gpio6,
+                        &I2cConfig::new().baudrate(Hertz::from(100_000u32)),
+                    ).unwrap();
+                    dryer = dryer.with_humidity_sensor(humidity::by_name(model, i2c, FreeRtos).unwrap());
+                }
+            }
+            match dotenv!("FAN_TACH_PULSES_PER_REV").parse::<u32>().unwrap() {
+                0 => {}
+                pulses_per_rev => {
+                    let tach = Tach::new(PinDriver::input(peripherals.pins.gpio7).unwrap(), pulses_per_rev).unwrap();
+                    dryer = dryer.with_tachometer(
+                        Box::new(tach),
+                        dotenv!("FAN_MAX_RPM").parse::<f32>().unwrap(),
+                        StallDetector::new(
+                            dotenv!("FAN_MIN_RPM").parse::<f32>().unwrap(),
+                            Duration::from_secs(dotenv!("FAN_STALL_SECS").parse::<u64>().unwrap()),
+                        ),
+                    );
+                }
+            }
+            if let Some(sensor) = bus.optional_sensor(dotenv!("ELEMENT_SENSOR_ROM")).unwrap() {
+                dryer = dryer.with_element_sensor(
+                    Box::new(FilteredSensor::new(sensor)),
+                    dotenv!("ELEMENT_MAX_TEMPERATURE").parse::<f32>().unwrap(),
+                );
+            }
+            if let Some(sensor) = bus.optional_sensor(dotenv!("AMBIENT_SENSOR_ROM")).unwrap() {
+                dryer = dryer.with_ambient_sensor(Box::new(FilteredSensor::new(sensor)));
+            }
 
-            for timer in timers_rx {
-                let res = dryer.start(timer, states_tx.clone());
+            for (session, cycle) in cycles_rx {
+                let res = dryer.run(session, cycle, states_tx.clone());
                 dryer.stop().unwrap();
-                states_tx.try_send(State::inactive()).unwrap();
-                if res.is_err() {
-                    panic!("{:?}", res.err().unwrap());
+                heater_sessions.end(session);
+                match res {
+                    Ok(Some(gains)) => match tuned.save(gains) {
+                        Ok(()) => info!("pid gains saved: {:?}", gains),
+                        Err(e) => error!("pid gains not saved: {}", e),
+                    },
+                    Ok(None) => {}
+                    Err(e) => {
+                        // Errors caused by a latched fault (e.g. a refused power on) end the cycle, not the firmware
+                        match heater_faults.get() {
+                            Some(fault) => error!("cycle stopped by {}: {:?}", fault, e),
+                            None => panic!("{:?}", e),
+                        }
+                        states_tx.try_send(State::inactive().with_session(Some(session)).with_fault(heater_faults.get())).unwrap();
+                    }
                 }
             }
         }),
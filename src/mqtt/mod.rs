@@ -1,22 +1,10 @@
-use anyhow::anyhow;
+use dryer_core::mqtt::{Command, MqttMessage};
 use embedded_svc::mqtt::client::{EventPayload, MessageId, QoS};
 use esp_idf_svc::mqtt::client::{EspMqttClient, MqttClientConfiguration};
-use serde::Deserialize;
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
 
-#[derive(Debug)]
-pub enum Command {
-    Start(Duration),
-    Stop,
-}
-
-#[derive(Debug, Deserialize)]
-struct StartOptions {
-    duration: u64,
-}
-
 pub struct Credentials {
     client_id: String,
     username: String,
@@ -35,14 +23,17 @@ impl Credentials {
     }
 }
 
+/// A decoded command, or the topic and the reason it couldn't be decoded
+pub type Incoming = Result<Command, (String, anyhow::Error)>;
+
 pub struct Mqtt<'a> {
-    messages_rx: mpsc::Receiver<Command>,
+    messages_rx: mpsc::Receiver<Incoming>,
     client: EspMqttClient<'a>,
 }
 
 impl Mqtt<'_> {
     pub fn new(credentials: Credentials) -> Result<Self, anyhow::Error> {
-        let (messages_tx, messages_rx) = mpsc::channel::<Command>();
+        let (messages_tx, messages_rx) = mpsc::channel::<Incoming>();
         let tx_cb = messages_tx.clone();
         let client = EspMqttClient::new_cb(
             credentials.url.as_str(),
@@ -58,20 +49,13 @@ impl Mqtt<'_> {
                     topic,
                     data,
                     details,
-                } => match topic {
-                    Some("/start") => {
-                        let val: StartOptions = serde_json::from_slice(data).unwrap();
-                        tx_cb
-                            .send(Command::Start(Duration::from_secs(val.duration)))
-                            .unwrap();
+                } => {
+                    if let Some(topic) = topic {
+                        // Malformed payloads go to the command loop as well, to be answered on /rejected
+                        let incoming = Command::parse(topic, data).map_err(|e| (topic.to_string(), e));
+                        tx_cb.send(incoming).unwrap();
                     }
-                    Some("/stop") => {
-                        tx_cb.send(Command::Stop).unwrap();
-                    }
-                    c => {
-                        println!("{:?}", c);
-                    }
-                },
+                }
                 _ => {}
             },
         )?;
@@ -91,7 +75,7 @@ impl Mqtt<'_> {
         }
     }
 
-    pub fn on_command<F: FnMut(&mut Self, Command) -> Result<(), anyhow::Error>>(
+    pub fn on_command<F: FnMut(&mut Self, Incoming) -> Result<(), anyhow::Error>>(
         &mut self,
         mut cb: F,
     ) -> Result<(), anyhow::Error> {
@@ -107,9 +91,3 @@ impl Mqtt<'_> {
         Ok(())
     }
 }
-
-pub trait MqttMessage {
-    fn to_string(&self) -> Result<String, anyhow::Error>;
-
-    fn topic(&self) -> &str;
-}
@@ -1,3 +1 @@
 pub mod remote;
-pub mod timer;
-pub mod limit;
\ No newline at end of file